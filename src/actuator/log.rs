@@ -0,0 +1,54 @@
+use super::Event;
+use crate::{
+    SETTINGS,
+    logger::{Log, Policy},
+};
+use anyhow::Result;
+use arrow::{
+    array::{BooleanArray, RecordBatch, StringArray, TimestampMillisecondArray, UInt16Array},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::instrument;
+
+const ACTUATOR: &str = "actuator";
+
+/// Writes events to the actuator log, a row group per event
+#[instrument(err, skip(receiver))]
+pub(super) async fn run(receiver: mpsc::Receiver<Event>) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(
+            "Timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            false,
+        ),
+        Field::new("Actuator", DataType::Utf8, false),
+        Field::new("Source", DataType::Utf8, false),
+        Field::new("Value", DataType::UInt16, false),
+        Field::new("ReadBack", DataType::UInt16, false),
+        Field::new("Verified", DataType::Boolean, false),
+    ]));
+    let policy = Policy {
+        flush: 1,
+        finish: SETTINGS.actuation.finish,
+    };
+    Log::new(ACTUATOR, schema, policy)
+        .run(receiver, |schema, event| {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(TimestampMillisecondArray::from(vec![
+                        event.date_time.timestamp_millis(),
+                    ])),
+                    Arc::new(StringArray::from(vec![event.actuator])),
+                    Arc::new(StringArray::from(vec![event.source.name()])),
+                    Arc::new(UInt16Array::from(vec![event.value])),
+                    Arc::new(UInt16Array::from(vec![event.read_back])),
+                    Arc::new(BooleanArray::from(vec![event.verified])),
+                ],
+            )?;
+            Ok((event.date_time, batch))
+        })
+        .await
+}
@@ -0,0 +1,483 @@
+use crate::{SETTINGS, output, settings::Actuator};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Days, Local, NaiveTime};
+use futures_util::future::{join_all, join3, select_all};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    future::pending,
+    io,
+    sync::{LazyLock, Mutex, PoisonError},
+};
+use tokio::{
+    select,
+    sync::{mpsc, watch},
+    task::{Builder, JoinHandle},
+    time::{Duration, MissedTickBehavior, interval, sleep},
+};
+use tokio_modbus::client::Context;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, instrument, warn};
+
+const SLEEP: Duration = Duration::from_secs(10);
+const CHANNEL_BUFFER: usize = 64;
+
+/// Actuator requests and states, shared with the HTTP server and the MQTT
+/// commander
+pub(crate) static ACTUATORS: LazyLock<Actuators> = LazyLock::new(Actuators::new);
+
+pub(crate) fn spawn(cancellation: CancellationToken) -> io::Result<JoinHandle<()>> {
+    Builder::new().name("actuator").spawn(Box::pin(async move {
+        loop {
+            // Returns once the defaults are restored, also when cancelled
+            let _ = run(&cancellation).await;
+            if cancellation.is_cancelled() {
+                warn!("actuator cancelled");
+                break;
+            }
+            warn!("loop actuator");
+            select! {
+                biased;
+                _ = cancellation.cancelled() => {
+                    warn!("actuator cancelled");
+                    break;
+                }
+                _ = sleep(SLEEP) => {},
+            };
+        }
+    }))
+}
+
+#[instrument(err, skip(cancellation))]
+async fn run(cancellation: &CancellationToken) -> Result<()> {
+    if SETTINGS.actuation.actuators.is_empty() {
+        cancellation.cancelled().await;
+        return Ok(());
+    }
+    // Stops the devices when the schedule or the log stops
+    let stop = cancellation.child_token();
+    let (sender, receiver) = mpsc::channel(CHANNEL_BUFFER);
+    let mut devices = BTreeMap::<_, Vec<_>>::new();
+    for actuator in &SETTINGS.actuation.actuators {
+        devices
+            .entry((actuator.address, actuator.unit))
+            .or_default()
+            .push(actuator);
+    }
+    let devices = join_all(
+        devices
+            .into_values()
+            .map(|actuators| retry(actuators, sender.clone(), &stop)),
+    );
+    drop(sender);
+    let schedule = async {
+        let result = select! {
+            biased;
+            _ = stop.cancelled() => Ok(()),
+            result = schedule() => result,
+        };
+        stop.cancel();
+        result
+    };
+    let log = async {
+        let result = log::run(receiver).await;
+        stop.cancel();
+        result
+    };
+    let (_, schedule, log) = join3(devices, schedule, log).await;
+    schedule?;
+    log
+}
+
+/// Restarts a device until stopped, a failure only switches its own actuators
+/// to their defaults
+async fn retry(
+    actuators: Vec<&'static Actuator>,
+    sender: mpsc::Sender<Event>,
+    stop: &CancellationToken,
+) {
+    loop {
+        // Returns once the defaults are restored, also when stopped
+        let _ = device(&actuators, sender.clone(), stop).await;
+        if stop.is_cancelled() {
+            break;
+        }
+        warn!("loop actuator {}", actuators[0].address);
+        select! {
+            biased;
+            _ = stop.cancelled() => break,
+            _ = sleep(SLEEP) => {},
+        };
+    }
+}
+
+/// Connects to a device and drives its actuators until stopped, they are
+/// switched to their defaults on every exit
+#[instrument(err, skip_all, fields(address = %actuators[0].address))]
+async fn device(
+    actuators: &[&'static Actuator],
+    sender: mpsc::Sender<Event>,
+    stop: &CancellationToken,
+) -> Result<()> {
+    let (address, unit) = (actuators[0].address, actuators[0].unit);
+    let result = select! {
+        biased;
+        _ = stop.cancelled() => Ok(()),
+        result = async {
+            let mut context = output::connect(address, unit).await?;
+            info!("Actuator: {address}");
+            drive(actuators, &mut context, sender).await
+        } => result,
+    };
+    let values = actuators
+        .iter()
+        .map(|actuator| (actuator.output, actuator.default))
+        .collect::<Vec<_>>();
+    output::restore(address, unit, &values).await;
+    result
+}
+
+/// Writes the requests as they change and restores the values that do not
+/// read back
+async fn drive(
+    actuators: &[&'static Actuator],
+    context: &mut Context,
+    sender: mpsc::Sender<Event>,
+) -> Result<()> {
+    let mut receivers = actuators
+        .iter()
+        .map(|actuator| {
+            let mut receiver = ACTUATORS.subscribe(&actuator.name);
+            receiver.mark_changed();
+            receiver
+        })
+        .collect::<Vec<_>>();
+    let mut interval = interval(Duration::from_secs(SETTINGS.actuation.verify));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval.tick().await;
+    loop {
+        select! {
+            index = changed(&mut receivers) => {
+                let index = index?;
+                let request = *receivers[index].borrow_and_update();
+                let event = apply(actuators[index], context, request.value, request.source).await?;
+                sender.send(event).await?;
+            }
+            _ = interval.tick() => {
+                for (actuator, receiver) in actuators.iter().zip(&receivers) {
+                    let value = receiver.borrow().value;
+                    let read_back = actuator.output.read(context).await?;
+                    if read_back != value {
+                        warn!("Actuator {} reads back {read_back} instead of {value}", actuator.name);
+                        let event = apply(actuator, context, value, Source::Restore).await?;
+                        sender.send(event).await?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Index of a changed request
+async fn changed(receivers: &mut [watch::Receiver<Request>]) -> Result<usize> {
+    let changed = receivers
+        .iter_mut()
+        .map(|receiver| Box::pin(receiver.changed()));
+    let (result, index, _) = select_all(changed).await;
+    result?;
+    Ok(index)
+}
+
+/// Writes and reads back a value
+async fn apply(
+    actuator: &Actuator,
+    context: &mut Context,
+    value: u16,
+    source: Source,
+) -> Result<Event> {
+    actuator.output.write(context, value).await?;
+    let read_back = actuator.output.read(context).await?;
+    let event = Event {
+        actuator: actuator.name.clone(),
+        date_time: Local::now(),
+        source,
+        value,
+        read_back,
+        verified: read_back == value,
+    };
+    info!(?event);
+    ACTUATORS.event(&event);
+    Ok(event)
+}
+
+/// Requests the scheduled values at the switch times
+async fn schedule() -> Result<()> {
+    let switches = SETTINGS
+        .actuation
+        .actuators
+        .iter()
+        .flat_map(|actuator| {
+            actuator
+                .schedule
+                .iter()
+                .map(move |switch| (actuator, switch))
+        })
+        .collect::<Vec<_>>();
+    let mut previous = Local::now();
+    loop {
+        let Some(next) = switches
+            .iter()
+            .map(|(_, switch)| next(previous, switch.at))
+            .min()
+        else {
+            return pending().await;
+        };
+        sleep((next - Local::now()).to_std().unwrap_or_default()).await;
+        for (actuator, switch) in &switches {
+            if self::next(previous, switch.at) == next {
+                ACTUATORS.request(&actuator.name, switch.value, Source::Schedule);
+            }
+        }
+        previous = next;
+    }
+}
+
+/// Next occurrence of a time of the day
+fn next(after: DateTime<Local>, at: NaiveTime) -> DateTime<Local> {
+    let mut date = after.date_naive();
+    loop {
+        // Skips the times missing on a daylight saving transition
+        if let Some(date_time) = date.and_time(at).and_local_timezone(Local).earliest()
+            && date_time > after
+        {
+            return date_time;
+        }
+        date = date + Days::new(1);
+    }
+}
+
+/// Scheduled value, the latest switch within a day
+fn scheduled(actuator: &Actuator, now: DateTime<Local>) -> Option<u16> {
+    let day_ago = now - Days::new(1);
+    actuator
+        .schedule
+        .iter()
+        .max_by_key(|switch| next(day_ago, switch.at))
+        .map(|switch| switch.value)
+}
+
+/// Actuator requests and latest events by name
+pub(crate) struct Actuators {
+    requests: BTreeMap<String, (&'static Actuator, watch::Sender<Request>)>,
+    events: Mutex<BTreeMap<String, Event>>,
+}
+
+impl Actuators {
+    fn new() -> Self {
+        let now = Local::now();
+        let requests = SETTINGS
+            .actuation
+            .actuators
+            .iter()
+            .map(|actuator| {
+                let request = match scheduled(actuator, now) {
+                    Some(value) => Request::new(value, Source::Schedule),
+                    None => Request::new(actuator.default, Source::Default),
+                };
+                (
+                    actuator.name.clone(),
+                    (actuator, watch::Sender::new(request)),
+                )
+            })
+            .collect();
+        Self {
+            requests,
+            events: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub(crate) fn states(&self) -> BTreeMap<String, State> {
+        let events = self.events.lock().unwrap_or_else(PoisonError::into_inner);
+        self.requests
+            .iter()
+            .map(|(name, (_, sender))| {
+                let state = State {
+                    request: *sender.borrow(),
+                    event: events.get(name).cloned(),
+                };
+                (name.clone(), state)
+            })
+            .collect()
+    }
+
+    /// Requests a value, held until the next scheduled switch
+    pub(crate) fn set(&self, actuator: &str, value: u16, source: Source) -> Result<Request> {
+        let Some((settings, _)) = self.requests.get(actuator) else {
+            bail!("unknown actuator `{actuator}`");
+        };
+        let limit = settings.output.limit();
+        if value > limit {
+            bail!("value `{value}` above `{limit}`");
+        }
+        Ok(self.request(actuator, value, source))
+    }
+
+    fn request(&self, actuator: &str, value: u16, source: Source) -> Request {
+        let request = Request::new(value, source);
+        info!("Actuator {actuator}: {request}");
+        self.requests[actuator].1.send_replace(request);
+        request
+    }
+
+    /// Requests the default unless another source requested a value since
+    pub(crate) fn release(&self, actuator: &str, source: Source) {
+        let (settings, sender) = &self.requests[actuator];
+        sender.send_if_modified(|request| {
+            if request.source != source {
+                return false;
+            }
+            *request = Request::new(settings.default, Source::Default);
+            info!("Actuator {actuator}: {request}");
+            true
+        });
+    }
+
+    fn subscribe(&self, actuator: &str) -> watch::Receiver<Request> {
+        self.requests[actuator].1.subscribe()
+    }
+
+    fn event(&self, event: &Event) {
+        let mut events = self.events.lock().unwrap_or_else(PoisonError::into_inner);
+        events.insert(event.actuator.clone(), event.clone());
+    }
+}
+
+/// Actuator state
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct State {
+    pub(crate) request: Request,
+    /// Latest event
+    pub(crate) event: Option<Event>,
+}
+
+/// Requested value
+#[derive(Clone, Copy, Debug, Serialize)]
+pub(crate) struct Request {
+    pub(crate) value: u16,
+    pub(crate) source: Source,
+    pub(crate) date_time: DateTime<Local>,
+}
+
+impl Request {
+    fn new(value: u16, source: Source) -> Self {
+        Self {
+            value,
+            source,
+            date_time: Local::now(),
+        }
+    }
+}
+
+impl Display for Request {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} by {} at {}", self.value, self.source, self.date_time)
+    }
+}
+
+/// Actuator event, a written value with its read back
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Event {
+    pub(crate) actuator: String,
+    pub(crate) date_time: DateTime<Local>,
+    pub(crate) source: Source,
+    pub(crate) value: u16,
+    pub(crate) read_back: u16,
+    pub(crate) verified: bool,
+}
+
+/// Value source
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Source {
+    Default,
+    Command,
+    Schedule,
+    Turbidostat,
+    /// Rewritten after reading back another value
+    Restore,
+}
+
+impl Source {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Command => "command",
+            Self::Schedule => "schedule",
+            Self::Turbidostat => "turbidostat",
+            Self::Restore => "restore",
+        }
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+mod log;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{Output, Switch};
+    use chrono::TimeZone as _;
+
+    fn at(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn on(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 6, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn actuator(schedule: &[(NaiveTime, u16)]) -> Actuator {
+        Actuator {
+            name: "light".to_owned(),
+            address: "127.0.0.1:502".parse().unwrap(),
+            unit: None,
+            output: Output::Coil { address: 0 },
+            default: 0,
+            schedule: schedule
+                .iter()
+                .map(|&(at, value)| Switch { at, value })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn next_switch() {
+        assert_eq!(next(on(10, 8, 0), at(9, 0)), on(10, 9, 0));
+        assert_eq!(next(on(10, 8, 0), at(7, 0)), on(11, 7, 0));
+        // Strictly after
+        assert_eq!(next(on(10, 8, 0), at(8, 0)), on(11, 8, 0));
+        assert_eq!(
+            next(on(30, 23, 30), at(0, 15)),
+            Local.with_ymd_and_hms(2026, 7, 1, 0, 15, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn scheduled_value() {
+        let light = actuator(&[(at(7, 0), 1), (at(19, 0), 0)]);
+        assert_eq!(scheduled(&light, on(10, 12, 0)), Some(1));
+        assert_eq!(scheduled(&light, on(10, 20, 0)), Some(0));
+        // The latest switch is yesterday's
+        assert_eq!(scheduled(&light, on(10, 6, 0)), Some(0));
+        assert_eq!(scheduled(&light, on(10, 7, 0)), Some(1));
+        assert_eq!(scheduled(&actuator(&[]), on(10, 12, 0)), None);
+    }
+}
@@ -0,0 +1,134 @@
+use super::{Event, receive};
+use crate::{
+    SETTINGS,
+    logger::{Log, Policy},
+};
+use anyhow::Result;
+use arrow::{
+    array::{Float64Array, RecordBatch, StringArray, TimestampMillisecondArray, UInt64Array},
+    datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+};
+use std::sync::Arc;
+use tokio::{
+    select,
+    sync::broadcast,
+    time::{Instant, sleep_until},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::instrument;
+
+const ALARM: &str = "alarm";
+
+/// Writes events to the alarm log, a row group per event, the file is finished
+/// on cancellation
+#[instrument(err, skip(receiver, cancellation))]
+pub(super) async fn run(
+    mut receiver: broadcast::Receiver<Event>,
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    let Some(settings) = &SETTINGS.alarm.log else {
+        cancellation.cancelled().await;
+        return Ok(());
+    };
+    let policy = Policy {
+        flush: 1,
+        finish: settings.finish,
+    };
+    let mut log = Log::new(ALARM, schema(), policy);
+    loop {
+        let deadline = log.deadline();
+        select! {
+            biased;
+            _ = cancellation.cancelled() => break,
+            _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                log.finish().await?;
+            }
+            event = receive(&mut receiver, "alarm log") => {
+                let event = event?;
+                let date_time = event.date_time;
+                let batch = batch(log.schema(), event)?;
+                log.write(date_time, &batch).await?;
+            }
+        }
+    }
+    log.finish().await?;
+    Ok(())
+}
+
+fn schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new(
+            "Timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            false,
+        ),
+        Field::new("Rule", DataType::Utf8, false),
+        Field::new("Sensor", DataType::Utf8, false),
+        Field::new("Identifier", DataType::UInt64, false),
+        Field::new("Name", DataType::Utf8, true),
+        Field::new("Condition", DataType::Utf8, false),
+        Field::new("State", DataType::Utf8, false),
+        Field::new("Value", DataType::Float64, false),
+    ]))
+}
+
+/// Row of an event
+fn batch(schema: &SchemaRef, event: Event) -> Result<RecordBatch> {
+    Ok(RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(TimestampMillisecondArray::from(vec![
+                event.date_time.timestamp_millis(),
+            ])),
+            Arc::new(StringArray::from(vec![event.rule])),
+            Arc::new(StringArray::from(vec![event.sensor.name()])),
+            Arc::new(UInt64Array::from(vec![event.identifier])),
+            Arc::new(StringArray::from(vec![event.name])),
+            Arc::new(StringArray::from(vec![event.condition.name()])),
+            Arc::new(StringArray::from(vec![event.state.name()])),
+            Arc::new(Float64Array::from(vec![event.value])),
+        ],
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{alarm::Condition, sensor::Sensor};
+    use chrono::Local;
+    use std::{
+        env::temp_dir,
+        fs::{read_dir, remove_dir_all},
+        process,
+    };
+
+    /// Several events of a reading share its date time
+    #[tokio::test]
+    async fn same_second() {
+        // An absolute folder replaces the configured output
+        let folder = temp_dir().join(format!("alarm-{}", process::id()));
+        let folder: &'static str = folder.to_str().unwrap().to_owned().leak();
+        let _ = remove_dir_all(folder);
+        let policy = Policy {
+            flush: 1,
+            finish: 1,
+        };
+        let mut log = Log::new(folder, schema(), policy);
+        let date_time = Local::now();
+        for identifier in [0x28ff01, 0x28ff02] {
+            let event = Event::new(
+                "high",
+                Sensor::Temperature,
+                identifier,
+                Condition::High,
+                40.0,
+                date_time,
+            );
+            let batch = batch(log.schema(), event).unwrap();
+            assert!(log.write(date_time, &batch).await.unwrap());
+        }
+        let files = read_dir(folder).unwrap().count();
+        remove_dir_all(folder).unwrap();
+        assert_eq!(files, 2);
+    }
+}
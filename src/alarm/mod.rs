@@ -0,0 +1,212 @@
+pub(crate) use self::tracker::{Condition, Event};
+
+use self::tracker::{State, Tracker};
+use crate::{
+    SETTINGS, metrics,
+    sensor::{Readings, Sensor},
+    temperature::Message as TemperatureMessage,
+    turbidity::Message as TurbidityMessage,
+};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use std::{
+    collections::HashMap,
+    io,
+    sync::{LazyLock, Mutex, PoisonError},
+};
+use tokio::{
+    select,
+    sync::broadcast::{self, error::RecvError},
+    task::{Builder, JoinHandle},
+    time::{Duration, sleep},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, instrument, warn};
+
+const SLEEP: Duration = Duration::from_secs(10);
+const CHANNEL_LENGTH: usize = 64;
+
+/// Alarms, shared with the sinks, the HTTP server and the MQTT commander
+pub(crate) static ALARMS: LazyLock<Alarms> = LazyLock::new(Alarms::new);
+
+pub(crate) fn spawn(
+    temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: CancellationToken,
+) -> io::Result<JoinHandle<()>> {
+    Builder::new().name("alarm").spawn(Box::pin(async move {
+        loop {
+            // Returns once the log is finished, also when cancelled
+            let _ = run(
+                temperature_receiver.resubscribe(),
+                turbidity_receiver.resubscribe(),
+                &cancellation,
+            )
+            .await;
+            if cancellation.is_cancelled() {
+                warn!("alarm cancelled");
+                break;
+            }
+            warn!("loop alarm");
+            select! {
+                biased;
+                _ = cancellation.cancelled() => {
+                    warn!("alarm cancelled");
+                    break;
+                }
+                _ = sleep(SLEEP) => {},
+            };
+        }
+    }))
+}
+
+#[instrument(err, skip(cancellation))]
+async fn run(
+    temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    let webhook = webhook::run(ALARMS.subscribe());
+    let smtp = smtp::run(ALARMS.subscribe());
+    // Returns on cancellation
+    let log = log::run(ALARMS.subscribe(), cancellation);
+    let temperature = evaluate(temperature_receiver);
+    let turbidity = evaluate(turbidity_receiver);
+    select! {
+        result = temperature => result?,
+        result = turbidity => result?,
+        result = webhook => result?,
+        result = smtp => result?,
+        result = log => result?,
+    }
+    Ok(())
+}
+
+/// Evaluates the rules against every reading
+async fn evaluate<T: Clone + Readings>(mut receiver: broadcast::Receiver<T>) -> Result<()> {
+    loop {
+        match receiver.recv().await {
+            Ok(message) => {
+                for (identifier, value) in message.readings() {
+                    ALARMS.update(T::SENSOR, identifier, message.date_time(), value);
+                }
+            }
+            Err(error @ RecvError::Lagged(_)) => {
+                warn!(%error);
+                metrics::lagged("alarm");
+            }
+            Err(error) => Err(error)?,
+        }
+    }
+}
+
+/// Alarm trackers by rule and sensor identifier, with the events sender
+pub(crate) struct Alarms {
+    trackers: Mutex<HashMap<(String, u64), Tracker>>,
+    sender: broadcast::Sender<Event>,
+}
+
+impl Alarms {
+    fn new() -> Self {
+        Self {
+            trackers: Mutex::new(HashMap::new()),
+            sender: broadcast::channel(CHANNEL_LENGTH).0,
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+
+    /// Raised and acknowledged alarms
+    pub(crate) fn active(&self) -> Vec<Event> {
+        let trackers = self.trackers.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut active = trackers
+            .values()
+            .filter_map(|tracker| tracker.active().cloned())
+            .collect::<Vec<_>>();
+        active.sort_by_key(|event| event.date_time);
+        active
+    }
+
+    pub(crate) fn acknowledge(&self, rule: &str, identifier: u64) -> Result<Event> {
+        let mut trackers = self.trackers.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(tracker) = trackers.get_mut(&(rule.to_owned(), identifier)) else {
+            bail!("unknown alarm `{rule}` for `{identifier:x}`");
+        };
+        let event = tracker.acknowledge()?;
+        info!(?event);
+        self.send(event.clone());
+        Ok(event)
+    }
+
+    fn update(&self, sensor: Sensor, identifier: u64, date_time: DateTime<Local>, value: f64) {
+        let mut trackers = self.trackers.lock().unwrap_or_else(PoisonError::into_inner);
+        for rule in &SETTINGS.alarm.rules {
+            if !rule.matches(sensor, identifier) {
+                continue;
+            }
+            let tracker = trackers.entry((rule.name.clone(), identifier)).or_default();
+            if let Some(event) = tracker.update(rule, identifier, date_time, value) {
+                info!(?event);
+                self.send(event);
+            }
+        }
+    }
+
+    /// Raises or clears the `watchdog <condition>` alarm, true when raised
+    pub(crate) fn watchdog(
+        &self,
+        sensor: Sensor,
+        identifier: u64,
+        condition: Condition,
+        date_time: DateTime<Local>,
+        value: f64,
+        violated: bool,
+    ) -> bool {
+        let rule = format!("watchdog {condition}");
+        let mut trackers = self.trackers.lock().unwrap_or_else(PoisonError::into_inner);
+        let key = (rule, identifier);
+        if !violated && !trackers.contains_key(&key) {
+            return false;
+        }
+        let event = || Event::new(&key.0, sensor, identifier, condition, value, date_time);
+        let tracker = trackers.entry(key.clone()).or_default();
+        match tracker.watch(event, date_time, value, violated) {
+            Some(event) => {
+                info!(?event);
+                let raised = event.state == State::Raised;
+                self.send(event);
+                raised
+            }
+            None => false,
+        }
+    }
+
+    fn send(&self, event: Event) {
+        // No sinks is not an error
+        let _ = self.sender.send(event);
+    }
+}
+
+/// Events of a sink, a slow sink drops the events it lags behind on
+pub(crate) async fn receive(
+    receiver: &mut broadcast::Receiver<Event>,
+    sink: &str,
+) -> Result<Event> {
+    loop {
+        match receiver.recv().await {
+            Ok(event) => return Ok(event),
+            Err(error @ RecvError::Lagged(_)) => {
+                warn!(%error);
+                metrics::lagged(sink);
+            }
+            Err(error) => Err(error)?,
+        }
+    }
+}
+
+mod log;
+mod smtp;
+mod tracker;
+mod webhook;
@@ -0,0 +1,51 @@
+use super::{Event, receive};
+use crate::{SETTINGS, settings::Security};
+use anyhow::Result;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport as _, Message, Tokio1Executor,
+    message::{Mailbox, header::ContentType},
+    transport::smtp::authentication::Credentials,
+};
+use std::future::pending;
+use tokio::sync::broadcast;
+use tracing::{instrument, warn};
+
+/// Emails events, failed deliveries are logged and dropped
+#[instrument(err, skip(receiver))]
+pub(super) async fn run(mut receiver: broadcast::Receiver<Event>) -> Result<()> {
+    let Some(settings) = &SETTINGS.alarm.smtp else {
+        return pending().await;
+    };
+    let mut builder = match settings.tls {
+        Security::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host),
+        Security::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)?,
+        Security::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host)?,
+    };
+    if let Some(port) = settings.port {
+        builder = builder.port(port);
+    }
+    if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+        builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+    let transport = builder.build();
+    let from = settings.from.parse::<Mailbox>()?;
+    let to = settings
+        .to
+        .iter()
+        .map(|to| to.parse())
+        .collect::<Result<Vec<Mailbox>, _>>()?;
+    loop {
+        let event = receive(&mut receiver, "alarm smtp").await?;
+        let mut builder = Message::builder()
+            .from(from.clone())
+            .subject(format!("BLCS alarm {} {}", event.rule, event.state))
+            .header(ContentType::TEXT_PLAIN);
+        for to in &to {
+            builder = builder.to(to.clone());
+        }
+        let message = builder.body(event.to_string())?;
+        if let Err(error) = transport.send(message).await {
+            warn!(%error);
+        }
+    }
+}
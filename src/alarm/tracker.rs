@@ -0,0 +1,396 @@
+use crate::{sensor::Sensor, settings::Rule};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+/// Alarm event
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Event {
+    pub(crate) rule: String,
+    pub(crate) sensor: Sensor,
+    pub(crate) identifier: u64,
+    pub(crate) name: Option<String>,
+    pub(crate) condition: Condition,
+    pub(crate) state: State,
+    pub(crate) value: f64,
+    pub(crate) date_time: DateTime<Local>,
+}
+
+impl Event {
+    /// Raised event
+    pub(super) fn new(
+        rule: &str,
+        sensor: Sensor,
+        identifier: u64,
+        condition: Condition,
+        value: f64,
+        date_time: DateTime<Local>,
+    ) -> Self {
+        Self {
+            rule: rule.to_owned(),
+            sensor,
+            identifier,
+            name: sensor.settings().name(identifier).map(ToOwned::to_owned),
+            condition,
+            state: State::Raised,
+            value,
+            date_time,
+        }
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} {}: {} ", self.rule, self.state, self.sensor)?;
+        match &self.name {
+            Some(name) => f.write_str(name)?,
+            None => write!(f, "{:x}", self.identifier)?,
+        }
+        write!(f, " {} {}", self.condition, self.value)?;
+        if let Some(unit) = self.sensor.unit() {
+            write!(f, " {unit}")?;
+        }
+        write!(f, " at {}", self.date_time)
+    }
+}
+
+/// Alarm condition
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Condition {
+    High,
+    Low,
+    Rate,
+    /// No new data
+    Stale,
+    /// Identical consecutive samples
+    Stuck,
+    /// Expected probe absent
+    Missing,
+}
+
+impl Condition {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::High => "high",
+            Self::Low => "low",
+            Self::Rate => "rate",
+            Self::Stale => "stale",
+            Self::Stuck => "stuck",
+            Self::Missing => "missing",
+        }
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Alarm state
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum State {
+    Raised,
+    Acknowledged,
+    Cleared,
+}
+
+impl State {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Raised => "raised",
+            Self::Acknowledged => "acknowledged",
+            Self::Cleared => "cleared",
+        }
+    }
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Rule {
+    pub(crate) fn matches(&self, sensor: Sensor, identifier: u64) -> bool {
+        self.sensor == sensor
+            && self.identifier.as_ref().is_none_or(|hex| {
+                u64::from_str_radix(hex, 16).is_ok_and(|expected| expected == identifier)
+            })
+    }
+
+    /// Violated condition, the thresholds are shifted by the deadband
+    fn condition(&self, value: f64, rate: Option<f64>, deadband: f64) -> Option<Condition> {
+        if let Some(high) = self.high
+            && value > high - deadband
+        {
+            return Some(Condition::High);
+        }
+        if let Some(low) = self.low
+            && value < low + deadband
+        {
+            return Some(Condition::Low);
+        }
+        if let Some(limit) = self.rate
+            && let Some(rate) = rate
+            && rate.abs() > limit
+        {
+            return Some(Condition::Rate);
+        }
+        None
+    }
+}
+
+/// Alarm state machine of a rule and a sensor: normal, pending for the rule
+/// duration, raised, optionally acknowledged, then cleared back to normal
+#[derive(Debug, Default)]
+pub(super) struct Tracker {
+    previous: Option<(DateTime<Local>, f64)>,
+    pending: Option<(Condition, DateTime<Local>)>,
+    active: Option<Event>,
+}
+
+impl Tracker {
+    /// Raised or acknowledged event
+    pub(super) fn active(&self) -> Option<&Event> {
+        self.active.as_ref()
+    }
+
+    pub(super) fn acknowledge(&mut self) -> Result<Event> {
+        match &mut self.active {
+            Some(event) if event.state == State::Raised => {
+                event.state = State::Acknowledged;
+                event.date_time = Local::now();
+                Ok(event.clone())
+            }
+            Some(_) => bail!("alarm already acknowledged"),
+            None => bail!("alarm not raised"),
+        }
+    }
+
+    /// Event on state change
+    pub(super) fn update(
+        &mut self,
+        rule: &Rule,
+        identifier: u64,
+        date_time: DateTime<Local>,
+        value: f64,
+    ) -> Option<Event> {
+        // Change per minute since the previous reading
+        let rate = self.previous.and_then(|(previous, previous_value)| {
+            let minutes = (date_time - previous).num_milliseconds() as f64 / 60_000.0;
+            (minutes > 0.0).then(|| (value - previous_value) / minutes)
+        });
+        self.previous = Some((date_time, value));
+        let deadband = if self.active.is_some() || self.pending.is_some() {
+            rule.hysteresis
+        } else {
+            0.0
+        };
+        let condition = rule.condition(value, rate, deadband);
+        match (&self.active, condition) {
+            (Some(_), None) => self.clear(date_time, value),
+            (Some(_), Some(_)) => None,
+            (None, None) => {
+                self.pending = None;
+                None
+            }
+            (None, Some(condition)) => {
+                let since = match self.pending {
+                    Some((pending, since)) if pending == condition => since,
+                    _ => self.pending.insert((condition, date_time)).1,
+                };
+                if (date_time - since).num_seconds() < rule.duration as _ {
+                    return None;
+                }
+                self.pending = None;
+                let event = Event::new(
+                    &rule.name,
+                    rule.sensor,
+                    identifier,
+                    condition,
+                    value,
+                    date_time,
+                );
+                Some(self.raise(event))
+            }
+        }
+    }
+
+    /// Event on a watchdog state change, raised without delay
+    pub(super) fn watch(
+        &mut self,
+        event: impl FnOnce() -> Event,
+        date_time: DateTime<Local>,
+        value: f64,
+        violated: bool,
+    ) -> Option<Event> {
+        match (&self.active, violated) {
+            (None, true) => Some(self.raise(event())),
+            (Some(_), false) => self.clear(date_time, value),
+            _ => None,
+        }
+    }
+
+    fn raise(&mut self, event: Event) -> Event {
+        self.active.insert(event).clone()
+    }
+
+    fn clear(&mut self, date_time: DateTime<Local>, value: f64) -> Option<Event> {
+        let active = self.active.take()?;
+        Some(Event {
+            state: State::Cleared,
+            value,
+            date_time,
+            ..active
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, TimeZone as _};
+
+    fn rule(high: Option<f64>, low: Option<f64>, rate: Option<f64>) -> Rule {
+        Rule {
+            name: "rule".to_owned(),
+            sensor: Sensor::Temperature,
+            identifier: Some("28ff01".to_owned()),
+            high,
+            low,
+            rate,
+            hysteresis: 0.0,
+            duration: 0,
+        }
+    }
+
+    fn at(seconds: i64) -> DateTime<Local> {
+        Local.timestamp_opt(1_700_000_000, 0).unwrap() + TimeDelta::seconds(seconds)
+    }
+
+    fn track(tracker: &mut Tracker, rule: &Rule, readings: &[(i64, f64)]) -> Vec<Option<State>> {
+        readings
+            .iter()
+            .map(|&(seconds, value)| {
+                tracker
+                    .update(rule, 0x28ff01, at(seconds), value)
+                    .map(|event| event.state)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn conditions() {
+        let rule = rule(Some(30.0), Some(10.0), Some(1.0));
+        let mut tracker = Tracker::default();
+        let event = tracker.update(&rule, 0x28ff01, at(0), 31.0).unwrap();
+        assert_eq!(
+            (event.condition, event.state),
+            (Condition::High, State::Raised)
+        );
+        assert_eq!(event.date_time, at(0));
+        let mut tracker = Tracker::default();
+        let event = tracker.update(&rule, 0x28ff01, at(0), 9.0).unwrap();
+        assert_eq!(event.condition, Condition::Low);
+        // Per minute
+        let mut tracker = Tracker::default();
+        assert!(tracker.update(&rule, 0x28ff01, at(0), 20.0).is_none());
+        assert!(tracker.update(&rule, 0x28ff01, at(60), 20.5).is_none());
+        let event = tracker.update(&rule, 0x28ff01, at(120), 22.5).unwrap();
+        assert_eq!(event.condition, Condition::Rate);
+    }
+
+    #[test]
+    fn hysteresis() {
+        let rule = Rule {
+            hysteresis: 1.0,
+            ..rule(Some(30.0), None, None)
+        };
+        let mut tracker = Tracker::default();
+        let states = track(
+            &mut tracker,
+            &rule,
+            &[
+                (0, 29.5),
+                (1, 30.5),
+                (2, 29.5),
+                (3, 28.9),
+                (4, 29.5),
+                (5, 30.1),
+            ],
+        );
+        assert_eq!(
+            states,
+            [
+                None,
+                Some(State::Raised),
+                None,
+                Some(State::Cleared),
+                None,
+                Some(State::Raised),
+            ]
+        );
+    }
+
+    #[test]
+    fn duration() {
+        let rule = Rule {
+            duration: 60,
+            ..rule(Some(30.0), None, None)
+        };
+        let mut tracker = Tracker::default();
+        let states = track(&mut tracker, &rule, &[(0, 31.0), (30, 31.0), (60, 31.0)]);
+        assert_eq!(states, [None, None, Some(State::Raised)]);
+        // Interrupted conditions start over
+        let mut tracker = Tracker::default();
+        let states = track(
+            &mut tracker,
+            &rule,
+            &[(0, 31.0), (30, 29.0), (60, 31.0), (90, 31.0), (120, 31.0)],
+        );
+        assert_eq!(states, [None, None, None, None, Some(State::Raised)]);
+    }
+
+    #[test]
+    fn acknowledge() {
+        let rule = rule(Some(30.0), None, None);
+        let mut tracker = Tracker::default();
+        assert!(tracker.acknowledge().is_err());
+        tracker.update(&rule, 0x28ff01, at(0), 31.0).unwrap();
+        assert_eq!(tracker.acknowledge().unwrap().state, State::Acknowledged);
+        assert!(tracker.acknowledge().is_err());
+        assert!(tracker.update(&rule, 0x28ff01, at(1), 32.0).is_none());
+        let event = tracker.update(&rule, 0x28ff01, at(2), 29.0).unwrap();
+        assert_eq!((event.state, event.value), (State::Cleared, 29.0));
+        assert!(tracker.active().is_none());
+    }
+
+    #[test]
+    fn watch() {
+        let mut tracker = Tracker::default();
+        let event = || Event::new("stale", Sensor::Turbidity, 1, Condition::Stale, 0.0, at(0));
+        assert!(tracker.watch(event, at(0), 0.0, false).is_none());
+        let raised = tracker.watch(event, at(0), 0.0, true).unwrap();
+        assert_eq!(raised.state, State::Raised);
+        assert!(tracker.watch(event, at(1), 0.0, true).is_none());
+        let cleared = tracker.watch(event, at(2), 1.0, false).unwrap();
+        assert_eq!((cleared.state, cleared.date_time), (State::Cleared, at(2)));
+    }
+
+    #[test]
+    fn matches() {
+        let rule = rule(Some(30.0), None, None);
+        assert!(rule.matches(Sensor::Temperature, 0x28ff01));
+        assert!(!rule.matches(Sensor::Temperature, 0x28ff02));
+        assert!(!rule.matches(Sensor::Turbidity, 0x28ff01));
+        let any = Rule {
+            identifier: None,
+            ..rule
+        };
+        assert!(any.matches(Sensor::Temperature, 0x28ff02));
+    }
+}
@@ -0,0 +1,30 @@
+use super::{Event, receive};
+use crate::SETTINGS;
+use anyhow::Result;
+use reqwest::Client;
+use std::future::pending;
+use tokio::{sync::broadcast, time::Duration};
+use tracing::{instrument, warn};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Posts events as JSON, failed deliveries are logged and dropped
+#[instrument(err, skip(receiver))]
+pub(super) async fn run(mut receiver: broadcast::Receiver<Event>) -> Result<()> {
+    let Some(settings) = &SETTINGS.alarm.webhook else {
+        return pending().await;
+    };
+    let client = Client::builder().timeout(TIMEOUT).build()?;
+    loop {
+        let event = receive(&mut receiver, "alarm webhook").await?;
+        let result = client
+            .post(&settings.url)
+            .json(&event)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(error) = result {
+            warn!(%error);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use super::{ANNOTATIONS, ANNOTATIONS_FOLDER, AUTHOR, Annotation, IDENTIFIER, SENSOR, TEXT};
+use crate::{
+    SETTINGS,
+    archive::TIMESTAMP,
+    logger::{Log, Policy},
+    metrics,
+    sensor::Sensor,
+};
+use anyhow::Result;
+use arrow::{
+    array::{RecordBatch, StringArray, TimestampMillisecondArray, UInt64Array},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+};
+use chrono::Local;
+use std::sync::Arc;
+use tokio::{
+    select,
+    sync::broadcast::{self, error::RecvError},
+    time::{Instant, sleep_until},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{instrument, warn};
+
+/// Writes annotations to the annotations log, a row group per annotation, the
+/// unscoped ones with `null` sensor and identifier, the file is finished on
+/// cancellation
+#[instrument(err, skip(receiver, cancellation))]
+pub(super) async fn run(
+    mut receiver: broadcast::Receiver<Annotation>,
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(
+            TIMESTAMP,
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            false,
+        ),
+        Field::new(AUTHOR, DataType::Utf8, false),
+        Field::new(TEXT, DataType::Utf8, false),
+        Field::new(SENSOR, DataType::Utf8, true),
+        Field::new(IDENTIFIER, DataType::UInt64, true),
+    ]));
+    let policy = Policy {
+        flush: 1,
+        finish: SETTINGS.annotation.finish,
+    };
+    let mut log = Log::new(ANNOTATIONS_FOLDER, schema, policy);
+    let mut written = Vec::new();
+    loop {
+        let deadline = log.deadline();
+        let annotation = select! {
+            biased;
+            _ = cancellation.cancelled() => break,
+            _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                log.finish().await?;
+                ANNOTATIONS.archived(&written);
+                written.clear();
+                continue;
+            }
+            annotation = receiver.recv() => match annotation {
+                Ok(annotation) => annotation,
+                Err(error @ RecvError::Lagged(_)) => {
+                    warn!(%error);
+                    metrics::lagged("annotation");
+                    continue;
+                }
+                Err(error) => Err(error)?,
+            },
+        };
+        let batch = RecordBatch::try_new(
+            log.schema().clone(),
+            vec![
+                Arc::new(TimestampMillisecondArray::from(vec![
+                    annotation.date_time.timestamp_millis(),
+                ])),
+                Arc::new(StringArray::from(vec![annotation.author.clone()])),
+                Arc::new(StringArray::from(vec![annotation.text.clone()])),
+                Arc::new(StringArray::from(vec![annotation.sensor.map(Sensor::name)])),
+                Arc::new(UInt64Array::from(vec![
+                    annotation
+                        .identifier
+                        .as_deref()
+                        .map(|identifier| u64::from_str_radix(identifier, 16))
+                        .transpose()?,
+                ])),
+            ],
+        )?;
+        written.push(annotation);
+        // Named by the write time as annotations may be timestamped in the past
+        if log.write(Local::now(), &batch).await? {
+            ANNOTATIONS.archived(&written);
+            written.clear();
+        }
+    }
+    log.finish().await?;
+    ANNOTATIONS.archived(&written);
+    Ok(())
+}
@@ -0,0 +1,243 @@
+use crate::{
+    archive::{self, TIMESTAMP},
+    sensor::Sensor,
+};
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Local, TimeZone as _};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    sync::{LazyLock, Mutex, PoisonError},
+};
+use tokio::{
+    select,
+    sync::broadcast,
+    task::{Builder, JoinHandle},
+    time::{Duration, sleep},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+const SLEEP: Duration = Duration::from_secs(10);
+const CHANNEL_LENGTH: usize = 64;
+const ANNOTATIONS_FOLDER: &str = "annotations";
+const AUTHOR: &str = "Author";
+const TEXT: &str = "Text";
+const SENSOR: &str = "Sensor";
+const IDENTIFIER: &str = "Identifier";
+
+/// Annotations, shared with the HTTP server and the MQTT commander
+pub(crate) static ANNOTATIONS: LazyLock<Annotations> = LazyLock::new(Annotations::new);
+
+pub(crate) fn spawn(cancellation: CancellationToken) -> io::Result<JoinHandle<()>> {
+    Builder::new()
+        .name("annotation")
+        .spawn(Box::pin(async move {
+            loop {
+                // Returns once the log is finished, also when cancelled
+                let _ = log::run(ANNOTATIONS.subscribe(), &cancellation).await;
+                if cancellation.is_cancelled() {
+                    warn!("annotation cancelled");
+                    break;
+                }
+                warn!("loop annotation");
+                select! {
+                    biased;
+                    _ = cancellation.cancelled() => {
+                        warn!("annotation cancelled");
+                        break;
+                    }
+                    _ = sleep(SLEEP) => {},
+                };
+            }
+        }))
+}
+
+/// Annotations sender with the annotations not in a finished file yet
+pub(crate) struct Annotations {
+    sender: broadcast::Sender<Annotation>,
+    pending: Mutex<Vec<Annotation>>,
+}
+
+impl Annotations {
+    fn new() -> Self {
+        Self {
+            sender: broadcast::channel(CHANNEL_LENGTH).0,
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Records an annotation, timestamped now unless given
+    pub(crate) fn annotate(&self, annotate: Annotate) -> Result<Annotation> {
+        if annotate.author.trim().is_empty() {
+            bail!("empty author");
+        }
+        if annotate.text.trim().is_empty() {
+            bail!("empty text");
+        }
+        if let Some(identifier) = &annotate.identifier {
+            if annotate.sensor.is_none() {
+                bail!("identifier `{identifier}` without sensor");
+            }
+            u64::from_str_radix(identifier, 16)?;
+        }
+        let annotation = Annotation {
+            date_time: annotate.date_time.unwrap_or_else(Local::now),
+            author: annotate.author,
+            text: annotate.text,
+            sensor: annotate.sensor,
+            identifier: annotate.identifier,
+        };
+        info!(?annotation);
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        pending.push(annotation.clone());
+        // No logger is not an error, the annotation stays pending
+        let _ = self.sender.send(annotation.clone());
+        Ok(annotation)
+    }
+
+    /// Annotations within `from..to` concerning the sensor scope, blocking
+    pub(crate) fn query(&self, query: &Query) -> Result<Vec<Annotation>> {
+        if query.from > query.to {
+            bail!("`from` is after `to`");
+        }
+        let identifier = query
+            .identifier
+            .as_deref()
+            .map(|identifier| u64::from_str_radix(identifier, 16))
+            .transpose()?;
+        let mut annotations = archived(query)?;
+        let pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        annotations.extend(
+            pending
+                .iter()
+                .filter(|annotation| query.from <= annotation.date_time)
+                .filter(|annotation| annotation.date_time < query.to)
+                .cloned(),
+        );
+        drop(pending);
+        annotations.retain(|annotation| annotation.concerns(query.sensor, identifier));
+        annotations.sort_by_key(|annotation| annotation.date_time);
+        Ok(annotations)
+    }
+
+    /// Annotations concerning the readings of an archive query, blocking
+    pub(crate) fn concerning(&self, query: &archive::Query) -> Result<Vec<Annotation>> {
+        self.query(&Query {
+            from: query.from,
+            to: query.to,
+            sensor: Some(query.sensor),
+            identifier: query.identifier.clone(),
+        })
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Annotation> {
+        self.sender.subscribe()
+    }
+
+    /// Forgets the pending annotations written to a finished file
+    fn archived(&self, annotations: &[Annotation]) {
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        pending.retain(|annotation| !annotations.contains(annotation));
+    }
+}
+
+/// Annotations of the finished files within `from..to`, blocking
+///
+/// Every file is scanned as annotations may be timestamped in the past.
+fn archived(query: &Query) -> Result<Vec<Annotation>> {
+    let paths = archive::folder_logs(ANNOTATIONS_FOLDER)?
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+    let data_frame = LazyFrame::scan_parquet_files(paths.into(), Default::default())?
+        .filter(
+            col(TIMESTAMP)
+                .gt_eq(lit(query.from.timestamp_millis()).cast(archive::timestamp()))
+                .and(
+                    col(TIMESTAMP).lt(lit(query.to.timestamp_millis()).cast(archive::timestamp())),
+                ),
+        )
+        .collect()?;
+    let timestamps = data_frame.column(TIMESTAMP)?.cast(&DataType::Int64)?;
+    let authors = data_frame.column(AUTHOR)?.str()?;
+    let texts = data_frame.column(TEXT)?.str()?;
+    let sensors = data_frame.column(SENSOR)?.str()?;
+    let identifiers = data_frame.column(IDENTIFIER)?.u64()?;
+    let mut annotations = Vec::with_capacity(data_frame.height());
+    for (index, timestamp) in timestamps.i64()?.into_iter().enumerate() {
+        let timestamp = timestamp.ok_or_else(|| anyhow!("null timestamp"))?;
+        annotations.push(Annotation {
+            date_time: Local
+                .timestamp_millis_opt(timestamp)
+                .single()
+                .ok_or_else(|| anyhow!("unexpected timestamp `{timestamp}`"))?,
+            author: authors.get(index).unwrap_or_default().to_owned(),
+            text: texts.get(index).unwrap_or_default().to_owned(),
+            sensor: sensors.get(index).map(str::parse).transpose()?,
+            identifier: identifiers
+                .get(index)
+                .map(|identifier| format!("{identifier:x}")),
+        });
+    }
+    Ok(annotations)
+}
+
+/// Timestamped operator note, concerning every sensor unless scoped
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct Annotation {
+    pub(crate) date_time: DateTime<Local>,
+    pub(crate) author: String,
+    pub(crate) text: String,
+    pub(crate) sensor: Option<Sensor>,
+    /// Hexadecimal sensor identifier, every sensor of the kind if unset
+    pub(crate) identifier: Option<String>,
+}
+
+impl Annotation {
+    fn concerns(&self, sensor: Option<Sensor>, identifier: Option<u64>) -> bool {
+        let Some(sensor) = sensor else {
+            return true;
+        };
+        if self.sensor.is_some_and(|scope| scope != sensor) {
+            return false;
+        }
+        let scope = self
+            .identifier
+            .as_deref()
+            .and_then(|identifier| u64::from_str_radix(identifier, 16).ok());
+        match (scope, identifier) {
+            (Some(scope), Some(identifier)) => scope == identifier,
+            _ => true,
+        }
+    }
+}
+
+/// Annotate command
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Annotate {
+    pub(crate) author: String,
+    pub(crate) text: String,
+    pub(crate) sensor: Option<Sensor>,
+    /// Hexadecimal sensor identifier
+    pub(crate) identifier: Option<String>,
+    /// Now unless given
+    pub(crate) date_time: Option<DateTime<Local>>,
+}
+
+/// Annotations query, every annotation within `from..to` unless scoped to a
+/// sensor
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Query {
+    pub(crate) from: DateTime<Local>,
+    pub(crate) to: DateTime<Local>,
+    pub(crate) sensor: Option<Sensor>,
+    /// Hexadecimal sensor identifier
+    pub(crate) identifier: Option<String>,
+}
+
+mod log;
@@ -0,0 +1,285 @@
+use crate::{
+    SETTINGS,
+    derived::{DERIVED_COLUMN, DERIVED_FOLDER},
+    sensor::Sensor,
+};
+use anyhow::{Result, bail};
+use arrow::ipc::reader::StreamReader;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone as _};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, read_dir},
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+const FORMAT: &str = "%Y-%m-%d-%H-%M-%S";
+const EXTENSION: &str = ".log.parquet";
+const IDENTIFIER: &str = "Identifier";
+const EXPERIMENT: &str = "Experiment";
+pub(crate) const TIMESTAMP: &str = "Timestamp";
+
+/// Archive query
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Query {
+    pub(crate) sensor: Sensor,
+    /// Hexadecimal sensor identifier
+    pub(crate) identifier: Option<String>,
+    pub(crate) from: DateTime<Local>,
+    pub(crate) to: DateTime<Local>,
+    /// Aggregation window (e.g. `1m`, `1h`)
+    pub(crate) every: Option<String>,
+    #[serde(default)]
+    pub(crate) agg: Aggregation,
+    /// Experiment name
+    pub(crate) experiment: Option<String>,
+    /// Includes the annotations concerning the readings
+    #[serde(default)]
+    pub(crate) annotations: bool,
+}
+
+/// Derived readings query, the derived channels are logged apart from the
+/// sensors and without experiment
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Derived {
+    /// Hexadecimal channel identifier
+    pub(crate) identifier: Option<String>,
+    pub(crate) from: DateTime<Local>,
+    pub(crate) to: DateTime<Local>,
+    /// Aggregation window (e.g. `1m`, `1h`)
+    pub(crate) every: Option<String>,
+    #[serde(default)]
+    pub(crate) agg: Aggregation,
+}
+
+/// Aggregation
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Aggregation {
+    #[default]
+    Mean,
+    Min,
+    Max,
+    First,
+    Last,
+    Sum,
+    Count,
+}
+
+impl Aggregation {
+    fn expr(self, expr: Expr) -> Expr {
+        match self {
+            Self::Mean => expr.mean(),
+            Self::Min => expr.min(),
+            Self::Max => expr.max(),
+            Self::First => expr.first(),
+            Self::Last => expr.last(),
+            Self::Sum => expr.sum(),
+            Self::Count => expr.count(),
+        }
+    }
+}
+
+/// Scans the logger output, blocking
+pub(crate) fn scan(query: &Query) -> Result<DataFrame> {
+    Ok(lazy(query)?.collect()?)
+}
+
+/// Empty data frame with the columns of a query, reading only the file
+/// metadata, blocking
+pub(crate) fn empty(query: &Query) -> Result<DataFrame> {
+    Ok(lazy(query)?.limit(0).collect()?)
+}
+
+/// Scans the derived log, blocking
+pub(crate) fn derived(query: &Derived) -> Result<DataFrame> {
+    if query.from > query.to {
+        bail!("`from` is after `to`");
+    }
+    let paths = files(DERIVED_FOLDER, query.from, query.to)?;
+    let schema = Schema::from_iter([
+        Field::new(IDENTIFIER.into(), DataType::UInt64),
+        Field::new(TIMESTAMP.into(), timestamp()),
+        Field::new(DERIVED_COLUMN.into(), DataType::Float64),
+    ]);
+    let lazy_frame = read(paths, schema, query.from, query.to)?;
+    let lazy_frame = aggregate(
+        lazy_frame,
+        DERIVED_COLUMN,
+        query.identifier.as_deref(),
+        query.every.as_deref(),
+        query.agg,
+    )?;
+    Ok(lazy_frame.collect()?)
+}
+
+fn lazy(query: &Query) -> Result<LazyFrame> {
+    if query.from > query.to {
+        bail!("`from` is after `to`");
+    }
+    let mut paths = files(query.sensor.name(), query.from, query.to)?;
+    let value = query.sensor.column();
+    let mut schema = Schema::from_iter([
+        Field::new(IDENTIFIER.into(), DataType::UInt64),
+        Field::new(TIMESTAMP.into(), timestamp()),
+        Field::new(value.into(), data_type(query.sensor)),
+    ]);
+    // Files logged before the experiments have no experiment column, nor
+    // readings of an experiment, and the row group statistics can not be read
+    // for a missing column
+    if query.experiment.is_some() {
+        paths = paths
+            .into_iter()
+            .filter_map(|path| match tagged(&path) {
+                Ok(true) => Some(Ok(path)),
+                Ok(false) => None,
+                Err(error) => Some(Err(error)),
+            })
+            .collect::<Result<_>>()?;
+        schema.insert(EXPERIMENT.into(), DataType::String);
+    }
+    let mut lazy_frame = read(paths, schema, query.from, query.to)?;
+    if let Some(experiment) = &query.experiment {
+        lazy_frame = lazy_frame.filter(col(EXPERIMENT).eq(lit(experiment.as_str())));
+    }
+    aggregate(
+        lazy_frame,
+        value,
+        query.identifier.as_deref(),
+        query.every.as_deref(),
+        query.agg,
+    )
+}
+
+/// Readings of the log files within `from..to`
+fn read(
+    paths: Vec<PathBuf>,
+    schema: Schema,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Result<LazyFrame> {
+    if paths.is_empty() {
+        return Ok(DataFrame::empty_with_schema(&schema).lazy());
+    }
+    let args = ScanArgsParquet {
+        schema: Some(Arc::new(schema)),
+        ..Default::default()
+    };
+    Ok(LazyFrame::scan_parquet_files(paths.into(), args)?.filter(
+        col(TIMESTAMP)
+            .gt_eq(lit(from.timestamp_millis()).cast(timestamp()))
+            .and(col(TIMESTAMP).lt(lit(to.timestamp_millis()).cast(timestamp()))),
+    ))
+}
+
+/// Readings of an identifier, aggregated over the windows if given
+fn aggregate(
+    mut lazy_frame: LazyFrame,
+    value: &str,
+    identifier: Option<&str>,
+    every: Option<&str>,
+    agg: Aggregation,
+) -> Result<LazyFrame> {
+    if let Some(identifier) = identifier {
+        let identifier = u64::from_str_radix(identifier, 16)?;
+        lazy_frame = lazy_frame.filter(col(IDENTIFIER).eq(lit(identifier)));
+    }
+    lazy_frame = lazy_frame.sort([IDENTIFIER, TIMESTAMP], Default::default());
+    if let Some(every) = every {
+        let every = Duration::try_parse(every)?;
+        lazy_frame = lazy_frame
+            .group_by_dynamic(
+                col(TIMESTAMP),
+                [col(IDENTIFIER)],
+                DynamicGroupOptions {
+                    every,
+                    period: every,
+                    offset: Duration::parse("0s"),
+                    ..Default::default()
+                },
+            )
+            .agg([agg.expr(col(value))]);
+    }
+    Ok(lazy_frame.select([col(IDENTIFIER), col(TIMESTAMP), col(value)]))
+}
+
+/// Whether a log file has the experiment column, reading its metadata
+fn tagged(path: &Path) -> Result<bool> {
+    Ok(ParquetReader::new(File::open(path)?)
+        .schema()?
+        .contains(EXPERIMENT))
+}
+
+/// Finished log files of an output folder overlapping `from..to`, in
+/// chronological order
+fn files(folder: &str, from: DateTime<Local>, to: DateTime<Local>) -> Result<Vec<PathBuf>> {
+    let logs = folder_logs(folder)?;
+    // A file ends when the next one starts
+    Ok(logs
+        .iter()
+        .enumerate()
+        .filter(|&(index, &(start, _))| {
+            start < to && logs.get(index + 1).is_none_or(|&(end, _)| end > from)
+        })
+        .map(|(_, (_, path))| path.clone())
+        .collect())
+}
+
+/// Finished log files with their start, in chronological order
+pub(crate) fn logs(sensor: Sensor) -> Result<Vec<(DateTime<Local>, PathBuf)>> {
+    folder_logs(sensor.name())
+}
+
+/// Finished log files of an output folder with their start, in chronological
+/// order
+pub(crate) fn folder_logs(folder: &str) -> Result<Vec<(DateTime<Local>, PathBuf)>> {
+    let folder = PathBuf::from(&SETTINGS.output).join(folder);
+    let mut logs = Vec::new();
+    if !folder.exists() {
+        return Ok(Vec::new());
+    }
+    for entry in read_dir(folder)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(start) = name.to_str().and_then(|name| name.strip_suffix(EXTENSION)) else {
+            continue;
+        };
+        let Some(start) = NaiveDateTime::parse_from_str(start, FORMAT)
+            .ok()
+            .and_then(|start| Local.from_local_datetime(&start).earliest())
+        else {
+            continue;
+        };
+        // Files in progress are empty until finished
+        if entry.metadata()?.len() > 0 {
+            logs.push((start, entry.path()));
+        }
+    }
+    logs.sort();
+    Ok(logs)
+}
+
+/// Data frame as Arrow record batches, blocking
+pub(crate) fn batches(
+    mut data_frame: DataFrame,
+) -> Result<(arrow::datatypes::SchemaRef, Vec<arrow::array::RecordBatch>)> {
+    let mut bytes = Vec::new();
+    IpcStreamWriter::new(&mut bytes)
+        .with_compat_level(CompatLevel::oldest())
+        .finish(&mut data_frame)?;
+    let reader = StreamReader::try_new(Cursor::new(bytes), None)?;
+    let schema = reader.schema();
+    Ok((schema, reader.collect::<Result<_, _>>()?))
+}
+
+fn data_type(sensor: Sensor) -> DataType {
+    match sensor {
+        Sensor::Temperature => DataType::Float32,
+        Sensor::Turbidity => DataType::UInt16,
+    }
+}
+
+pub(crate) fn timestamp() -> DataType {
+    DataType::Datetime(TimeUnit::Milliseconds, None)
+}
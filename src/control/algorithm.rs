@@ -0,0 +1,153 @@
+use crate::settings::Mode;
+use chrono::{DateTime, Local};
+
+/// Output limit in percent
+const MAX: f64 = 100.0;
+
+/// Control algorithm state
+#[derive(Debug, Default)]
+pub(super) struct Algorithm {
+    /// Error integrated over seconds
+    integral: f64,
+    previous: Option<(DateTime<Local>, f64)>,
+    output: f64,
+}
+
+impl Algorithm {
+    /// Output in percent, from -100 (full cooling) to 100 (full heating)
+    pub(super) fn update(
+        &mut self,
+        mode: Mode,
+        setpoint: f64,
+        process_value: f64,
+        date_time: DateTime<Local>,
+    ) -> f64 {
+        let error = setpoint - process_value;
+        self.output = match mode {
+            Mode::OnOff { hysteresis } => {
+                if error > hysteresis {
+                    MAX
+                } else if error < -hysteresis {
+                    -MAX
+                } else if self.output * error > 0.0 {
+                    // Setpoint not reached yet
+                    self.output
+                } else {
+                    0.0
+                }
+            }
+            Mode::Pid { kp, ki, kd } => {
+                let (seconds, derivative) = match self.previous {
+                    Some((previous, previous_value)) => {
+                        let seconds = (date_time - previous).num_milliseconds() as f64 / 1000.0;
+                        // On the measurement, so setpoint changes do not kick
+                        let derivative = if seconds > 0.0 {
+                            (previous_value - process_value) / seconds
+                        } else {
+                            0.0
+                        };
+                        (seconds.max(0.0), derivative)
+                    }
+                    None => (0.0, 0.0),
+                };
+                let integral = self.integral + error * seconds;
+                let output = kp * error + ki * integral + kd * derivative;
+                // Anti-windup, no integration further into saturation
+                if output.abs() < MAX || output.signum() != error.signum() {
+                    self.integral = integral;
+                }
+                output.clamp(-MAX, MAX)
+            }
+        };
+        self.previous = Some((date_time, process_value));
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, TimeZone as _};
+
+    fn at(seconds: i64) -> DateTime<Local> {
+        Local.timestamp_opt(1_700_000_000, 0).unwrap() + TimeDelta::seconds(seconds)
+    }
+
+    fn outputs(mode: Mode, steps: &[(i64, f64, f64)]) -> Vec<f64> {
+        let mut algorithm = Algorithm::default();
+        steps
+            .iter()
+            .map(|&(seconds, setpoint, process_value)| {
+                algorithm.update(mode, setpoint, process_value, at(seconds))
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+        }
+    }
+
+    #[test]
+    fn on_off() {
+        let mode = Mode::OnOff { hysteresis: 0.5 };
+        let steps = [36.0, 36.8, 37.1, 36.8, 37.6, 37.2, 36.9, 36.4];
+        let steps = steps
+            .iter()
+            .enumerate()
+            .map(|(index, &process_value)| (index as i64, 37.0, process_value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outputs(mode, &steps),
+            [100.0, 100.0, 0.0, 0.0, -100.0, -100.0, 0.0, 100.0]
+        );
+    }
+
+    #[test]
+    fn proportional() {
+        let mode = Mode::Pid {
+            kp: 10.0,
+            ki: 0.0,
+            kd: 0.0,
+        };
+        let steps = [(0, 37.0, 35.0), (1, 37.0, 20.0), (2, 37.0, 50.0)];
+        assert_close(&outputs(mode, &steps), &[20.0, 100.0, -100.0]);
+    }
+
+    #[test]
+    fn integral() {
+        let mode = Mode::Pid {
+            kp: 0.0,
+            ki: 1.0,
+            kd: 0.0,
+        };
+        let steps = [(0, 37.0, 35.0), (10, 37.0, 35.0), (20, 37.0, 35.0)];
+        assert_close(&outputs(mode, &steps), &[0.0, 20.0, 40.0]);
+    }
+
+    #[test]
+    fn anti_windup() {
+        let mode = Mode::Pid {
+            kp: 0.0,
+            ki: 10.0,
+            kd: 0.0,
+        };
+        // The saturated step is not integrated, so the output turns at once
+        let steps = [(0, 37.0, 35.0), (10, 37.0, 35.0), (20, 37.0, 37.1)];
+        assert_close(&outputs(mode, &steps), &[0.0, 100.0, -10.0]);
+    }
+
+    #[test]
+    fn derivative() {
+        let mode = Mode::Pid {
+            kp: 0.0,
+            ki: 0.0,
+            kd: 10.0,
+        };
+        // A setpoint change does not kick
+        let steps = [(0, 37.0, 36.0), (10, 37.0, 36.5), (20, 40.0, 36.5)];
+        assert_close(&outputs(mode, &steps), &[0.0, -0.5, 0.0]);
+    }
+}
@@ -0,0 +1,57 @@
+use super::Step;
+use crate::{
+    SETTINGS,
+    logger::{Log, Policy},
+};
+use anyhow::Result;
+use arrow::{
+    array::{Float64Array, RecordBatch, StringArray, TimestampMillisecondArray, UInt16Array},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::instrument;
+
+const CONTROL: &str = "control";
+
+/// Writes the control steps with the written outputs, `null` for a missing
+/// output
+#[instrument(err, skip(receiver))]
+pub(super) async fn run(receiver: mpsc::Receiver<Step>) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(
+            "Timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            false,
+        ),
+        Field::new("Controller", DataType::Utf8, false),
+        Field::new("ProcessValue", DataType::Float64, false),
+        Field::new("Setpoint", DataType::Float64, false),
+        Field::new("Output", DataType::Float64, false),
+        Field::new("Heater", DataType::UInt16, true),
+        Field::new("Cooler", DataType::UInt16, true),
+    ]));
+    let policy = Policy {
+        flush: SETTINGS.control.flush,
+        finish: SETTINGS.control.finish,
+    };
+    Log::new(CONTROL, schema, policy)
+        .run(receiver, |schema, step| {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(TimestampMillisecondArray::from(vec![
+                        step.date_time.timestamp_millis(),
+                    ])),
+                    Arc::new(StringArray::from(vec![step.controller])),
+                    Arc::new(Float64Array::from(vec![step.process_value])),
+                    Arc::new(Float64Array::from(vec![step.setpoint])),
+                    Arc::new(Float64Array::from(vec![step.output])),
+                    Arc::new(UInt16Array::from(vec![step.heater])),
+                    Arc::new(UInt16Array::from(vec![step.cooler])),
+                ],
+            )?;
+            Ok((step.date_time, batch))
+        })
+        .await
+}
@@ -0,0 +1,278 @@
+use self::algorithm::Algorithm;
+use crate::{
+    SETTINGS, metrics, output,
+    settings::{Controller, Output},
+    temperature::Message as TemperatureMessage,
+};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use futures_util::future::{join, join_all};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    io,
+    sync::{LazyLock, Mutex, PoisonError},
+};
+use tokio::{
+    select,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc,
+    },
+    task::{Builder, JoinHandle},
+    time::{Duration, Instant, sleep, timeout_at},
+};
+use tokio_modbus::client::Context;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, instrument, warn};
+
+const SLEEP: Duration = Duration::from_secs(10);
+const CHANNEL_BUFFER: usize = 64;
+
+/// Controller states, shared with the HTTP server and the MQTT commander
+pub(crate) static CONTROLLERS: LazyLock<Controllers> = LazyLock::new(Controllers::new);
+
+pub(crate) fn spawn(
+    temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    cancellation: CancellationToken,
+) -> io::Result<JoinHandle<()>> {
+    Builder::new().name("control").spawn(Box::pin(async move {
+        loop {
+            // Returns once the outputs are switched off, also when cancelled
+            let _ = run(temperature_receiver.resubscribe(), &cancellation).await;
+            if cancellation.is_cancelled() {
+                warn!("control cancelled");
+                break;
+            }
+            warn!("loop control");
+            select! {
+                biased;
+                _ = cancellation.cancelled() => {
+                    warn!("control cancelled");
+                    break;
+                }
+                _ = sleep(SLEEP) => {},
+            };
+        }
+    }))
+}
+
+#[instrument(err, skip(cancellation))]
+async fn run(
+    temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    if SETTINGS.control.controllers.is_empty() {
+        cancellation.cancelled().await;
+        return Ok(());
+    }
+    // Stops the controllers when the log stops
+    let stop = cancellation.child_token();
+    let (sender, receiver) = mpsc::channel(CHANNEL_BUFFER);
+    let controllers = join_all(
+        SETTINGS
+            .control
+            .controllers
+            .iter()
+            .map(|settings| retry(settings, &temperature_receiver, sender.clone(), &stop)),
+    );
+    drop(sender);
+    let log = async {
+        let result = log::run(receiver).await;
+        stop.cancel();
+        result
+    };
+    join(controllers, log).await.1
+}
+
+/// Restarts a controller until stopped, a failure only switches off its own
+/// outputs
+async fn retry(
+    settings: &'static Controller,
+    receiver: &broadcast::Receiver<TemperatureMessage>,
+    sender: mpsc::Sender<Step>,
+    stop: &CancellationToken,
+) {
+    loop {
+        // Returns once the outputs are switched off, also when stopped
+        let _ = control(settings, receiver.resubscribe(), sender.clone(), stop).await;
+        if stop.is_cancelled() {
+            break;
+        }
+        warn!("loop control {}", settings.name);
+        select! {
+            biased;
+            _ = stop.cancelled() => break,
+            _ = sleep(SLEEP) => {},
+        };
+    }
+}
+
+/// Connects to the outputs and steps the controller until stopped, the outputs
+/// are switched off on every exit
+#[instrument(err, skip_all, fields(controller = %settings.name))]
+async fn control(
+    settings: &'static Controller,
+    receiver: broadcast::Receiver<TemperatureMessage>,
+    sender: mpsc::Sender<Step>,
+    stop: &CancellationToken,
+) -> Result<()> {
+    let address = settings.address;
+    let result = select! {
+        biased;
+        _ = stop.cancelled() => Ok(()),
+        result = async {
+            let mut context = output::connect(address, settings.unit).await?;
+            info!("Control {}: {address}", settings.name);
+            regulate(settings, &mut context, receiver, sender).await
+        } => result,
+    };
+    let values = settings
+        .heater
+        .iter()
+        .chain(&settings.cooler)
+        .map(|&output| (output, 0))
+        .collect::<Vec<_>>();
+    output::restore(address, settings.unit, &values).await;
+    result
+}
+
+async fn regulate(
+    settings: &'static Controller,
+    context: &mut Context,
+    mut receiver: broadcast::Receiver<TemperatureMessage>,
+    sender: mpsc::Sender<Step>,
+) -> Result<()> {
+    let probe = u64::from_str_radix(&settings.probe, 16)?;
+    // Outputs are not left on without the process value
+    let stale = Duration::from_secs(10 * SETTINGS.temperature.interval);
+    let mut deadline = Instant::now() + stale;
+    let mut algorithm = Algorithm::default();
+    loop {
+        let Ok(message) = timeout_at(deadline, receiver.recv()).await else {
+            bail!("no reading of the `{}` probe", settings.probe);
+        };
+        let message = match message {
+            Ok(message) => message,
+            Err(error @ RecvError::Lagged(_)) => {
+                warn!(%error);
+                metrics::lagged("control");
+                continue;
+            }
+            Err(error) => Err(error)?,
+        };
+        let Some(index) = message.identifiers.iter().position(|&id| id == probe) else {
+            continue;
+        };
+        deadline = Instant::now() + stale;
+        let process_value = message.values[index] as f64;
+        let setpoint = CONTROLLERS.setpoint(&settings.name);
+        let output = algorithm.update(settings.mode, setpoint, process_value, message.date_time);
+        let heater = drive(context, settings.heater, output).await?;
+        let cooler = drive(context, settings.cooler, -output).await?;
+        let step = Step {
+            controller: settings.name.clone(),
+            date_time: message.date_time,
+            process_value,
+            setpoint,
+            output,
+            heater,
+            cooler,
+        };
+        debug!(?step);
+        CONTROLLERS.step(&step);
+        sender.send(step).await?;
+    }
+}
+
+/// Writes an output in percent, off below zero, returns the written value
+async fn drive(context: &mut Context, output: Option<Output>, percent: f64) -> Result<Option<u16>> {
+    let Some(output) = output else {
+        return Ok(None);
+    };
+    let value = output.scale(percent.max(0.0));
+    output.write(context, value).await?;
+    Ok(Some(value))
+}
+
+/// Controller states by name
+pub(crate) struct Controllers {
+    states: Mutex<BTreeMap<String, State>>,
+}
+
+impl Controllers {
+    fn new() -> Self {
+        let states = SETTINGS
+            .control
+            .controllers
+            .iter()
+            .map(|controller| {
+                let state = State {
+                    setpoint: controller.setpoint,
+                    step: None,
+                };
+                (controller.name.clone(), state)
+            })
+            .collect();
+        Self {
+            states: Mutex::new(states),
+        }
+    }
+
+    pub(crate) fn states(&self) -> BTreeMap<String, State> {
+        self.states
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Changes the setpoint, applied from the next step
+    pub(crate) fn set(&self, controller: &str, setpoint: f64) -> Result<State> {
+        if !setpoint.is_finite() {
+            bail!("invalid setpoint `{setpoint}`");
+        }
+        let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(state) = states.get_mut(controller) else {
+            bail!("unknown controller `{controller}`");
+        };
+        info!("Setpoint {controller}: {} -> {setpoint}", state.setpoint);
+        state.setpoint = setpoint;
+        Ok(state.clone())
+    }
+
+    fn setpoint(&self, controller: &str) -> f64 {
+        let states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        states[controller].setpoint
+    }
+
+    fn step(&self, step: &Step) {
+        let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(state) = states.get_mut(&step.controller) {
+            state.step = Some(step.clone());
+        }
+    }
+}
+
+/// Controller state
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct State {
+    pub(crate) setpoint: f64,
+    /// Latest step
+    pub(crate) step: Option<Step>,
+}
+
+/// Control step with the written outputs
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Step {
+    pub(crate) controller: String,
+    pub(crate) date_time: DateTime<Local>,
+    pub(crate) process_value: f64,
+    pub(crate) setpoint: f64,
+    /// Percent, negative for cooling
+    pub(crate) output: f64,
+    pub(crate) heater: Option<u16>,
+    pub(crate) cooler: Option<u16>,
+}
+
+mod algorithm;
+mod log;
@@ -0,0 +1,392 @@
+use crate::sensor::Sensor;
+use anyhow::{Error, Result, bail};
+use chrono::{DateTime, Local};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    iter::Peekable,
+    str::{Chars, FromStr},
+};
+
+/// Latest readings with their date time by sensor and identifier
+pub(super) type Values = BTreeMap<(Sensor, u64), (DateTime<Local>, f64)>;
+
+/// Arithmetic expression over the latest readings
+///
+/// Readings are `sensor.identifier`, the identifier hexadecimal or a configured
+/// name (e.g. `temperature.28ff01`, `turbidity.inlet`). Operators are `+`, `-`,
+/// `*`, `/` and `^`, functions are `mean`, `min`, `max`, `sum`, `abs`, `sqrt`,
+/// `ln`, `log10` and `exp`.
+#[derive(Clone, Debug)]
+pub(super) enum Expression {
+    Number(f64),
+    Reading(Sensor, u64),
+    Negation(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    Function(Function, Vec<Expression>),
+}
+
+impl Expression {
+    /// Value, unless a reading is missing
+    pub(super) fn evaluate(&self, values: &Values) -> Option<f64> {
+        Some(match self {
+            Self::Number(number) => *number,
+            Self::Reading(sensor, identifier) => values.get(&(*sensor, *identifier))?.1,
+            Self::Negation(expression) => -expression.evaluate(values)?,
+            Self::Binary(operator, left, right) => {
+                let (left, right) = (left.evaluate(values)?, right.evaluate(values)?);
+                match operator {
+                    Operator::Add => left + right,
+                    Operator::Subtract => left - right,
+                    Operator::Multiply => left * right,
+                    Operator::Divide => left / right,
+                    Operator::Power => left.powf(right),
+                }
+            }
+            Self::Function(function, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(values))
+                    .collect::<Option<Vec<_>>>()?;
+                function.apply(&arguments)
+            }
+        })
+    }
+
+    /// Sensors of the readings
+    pub(super) fn sensors(&self) -> BTreeSet<Sensor> {
+        let mut sensors = BTreeSet::new();
+        self.visit(&mut |expression| {
+            if let Self::Reading(sensor, _) = expression {
+                sensors.insert(*sensor);
+            }
+        });
+        sensors
+    }
+
+    fn visit(&self, f: &mut impl FnMut(&Self)) {
+        f(self);
+        match self {
+            Self::Number(_) | Self::Reading(..) => {}
+            Self::Negation(expression) => expression.visit(f),
+            Self::Binary(_, left, right) => {
+                left.visit(f);
+                right.visit(f);
+            }
+            Self::Function(_, arguments) => {
+                for argument in arguments {
+                    argument.visit(f);
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: source.chars().peekable(),
+        };
+        let expression = parser.sum()?;
+        if let Some(char) = parser.next() {
+            bail!("unexpected `{char}` in `{source}`");
+        }
+        Ok(expression)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Function {
+    Mean,
+    Min,
+    Max,
+    Sum,
+    Abs,
+    Sqrt,
+    Ln,
+    Log10,
+    Exp,
+}
+
+impl Function {
+    fn new(name: &str, arguments: usize) -> Result<Self> {
+        let function = match name {
+            "mean" => Self::Mean,
+            "min" => Self::Min,
+            "max" => Self::Max,
+            "sum" => Self::Sum,
+            "abs" => Self::Abs,
+            "sqrt" => Self::Sqrt,
+            "ln" => Self::Ln,
+            "log10" => Self::Log10,
+            "exp" => Self::Exp,
+            _ => bail!("unknown function `{name}`"),
+        };
+        match function {
+            Self::Mean | Self::Min | Self::Max | Self::Sum if arguments == 0 => {
+                bail!("`{name}` without arguments")
+            }
+            Self::Abs | Self::Sqrt | Self::Ln | Self::Log10 | Self::Exp if arguments != 1 => {
+                bail!("`{name}` with {arguments} arguments instead of 1")
+            }
+            _ => Ok(function),
+        }
+    }
+
+    fn apply(self, arguments: &[f64]) -> f64 {
+        match self {
+            Self::Mean => arguments.iter().sum::<f64>() / arguments.len() as f64,
+            Self::Min => arguments.iter().copied().fold(f64::INFINITY, f64::min),
+            Self::Max => arguments.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Self::Sum => arguments.iter().sum(),
+            Self::Abs => arguments[0].abs(),
+            Self::Sqrt => arguments[0].sqrt(),
+            Self::Ln => arguments[0].ln(),
+            Self::Log10 => arguments[0].log10(),
+            Self::Exp => arguments[0].exp(),
+        }
+    }
+}
+
+/// Recursive descent parser, the power is right associative and binds tighter
+/// than the negation
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn sum(&mut self) -> Result<Expression> {
+        let mut expression = self.product()?;
+        loop {
+            let operator = match self.peek() {
+                Some('+') => Operator::Add,
+                Some('-') => Operator::Subtract,
+                _ => return Ok(expression),
+            };
+            self.next();
+            expression = Expression::Binary(operator, expression.into(), self.product()?.into());
+        }
+    }
+
+    fn product(&mut self) -> Result<Expression> {
+        let mut expression = self.unary()?;
+        loop {
+            let operator = match self.peek() {
+                Some('*') => Operator::Multiply,
+                Some('/') => Operator::Divide,
+                _ => return Ok(expression),
+            };
+            self.next();
+            expression = Expression::Binary(operator, expression.into(), self.unary()?.into());
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression> {
+        if self.peek() == Some('-') {
+            self.next();
+            return Ok(Expression::Negation(self.unary()?.into()));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expression> {
+        let base = self.primary()?;
+        if self.peek() != Some('^') {
+            return Ok(base);
+        }
+        self.next();
+        let exponent = self.unary()?;
+        Ok(Expression::Binary(
+            Operator::Power,
+            base.into(),
+            exponent.into(),
+        ))
+    }
+
+    fn primary(&mut self) -> Result<Expression> {
+        match self.peek() {
+            Some('(') => {
+                self.next();
+                let expression = self.sum()?;
+                self.expect(')')?;
+                Ok(expression)
+            }
+            Some(char) if char.is_ascii_digit() || char == '.' => {
+                let mut number = self.word(|char| char.is_ascii_digit() || char == '.');
+                // The sign of the exponent is not an operator
+                if let Some(exponent) = self.chars.next_if(|&char| char == 'e' || char == 'E') {
+                    number.push(exponent);
+                    if let Some(sign) = self.chars.next_if(|&char| char == '+' || char == '-') {
+                        number.push(sign);
+                    }
+                    number.push_str(&self.word(|char| char.is_ascii_digit()));
+                }
+                Ok(Expression::Number(number.parse().map_err(|error| {
+                    Error::new(error).context(format!("number `{number}`"))
+                })?))
+            }
+            Some(char) if char.is_ascii_alphabetic() => {
+                let name = self.word(|char| char.is_ascii_alphanumeric() || char == '_');
+                match self.peek() {
+                    Some('(') => {
+                        self.next();
+                        let mut arguments = Vec::new();
+                        if self.peek() != Some(')') {
+                            arguments.push(self.sum()?);
+                            while self.peek() == Some(',') {
+                                self.next();
+                                arguments.push(self.sum()?);
+                            }
+                        }
+                        self.expect(')')?;
+                        let function = Function::new(&name, arguments.len())?;
+                        Ok(Expression::Function(function, arguments))
+                    }
+                    Some('.') => {
+                        self.next();
+                        let sensor = name.parse::<Sensor>()?;
+                        let identifier =
+                            self.word(|char| char.is_ascii_alphanumeric() || char == '_');
+                        Ok(Expression::Reading(sensor, reading(sensor, &identifier)?))
+                    }
+                    _ => bail!("unexpected `{name}`, expected a function or a reading"),
+                }
+            }
+            Some(char) => bail!("unexpected `{char}`"),
+            None => bail!("unexpected end"),
+        }
+    }
+
+    fn word(&mut self, f: impl Fn(char) -> bool) -> String {
+        let mut word = String::new();
+        while let Some(&char) = self.chars.peek()
+            && f(char)
+        {
+            word.push(char);
+            self.chars.next();
+        }
+        word
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        match self.next() {
+            Some(char) if char == expected => Ok(()),
+            Some(char) => bail!("unexpected `{char}`, expected `{expected}`"),
+            None => bail!("unexpected end, expected `{expected}`"),
+        }
+    }
+
+    /// Next character, skipping the whitespace
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|char| char.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        self.peek()?;
+        self.chars.next()
+    }
+}
+
+/// Identifier of a reading, a configured name or a hexadecimal identifier
+fn reading(sensor: Sensor, identifier: &str) -> Result<u64> {
+    let named = sensor
+        .settings()
+        .names
+        .iter()
+        .find(|(_, name)| *name == identifier)
+        .map(|(hex, _)| hex.as_str());
+    let hex = named.unwrap_or(identifier);
+    match u64::from_str_radix(hex, 16) {
+        Ok(identifier) => Ok(identifier),
+        Err(error) => bail!("unknown {sensor} `{identifier}`: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(source: &str) -> f64 {
+        let expression = source.parse::<Expression>().unwrap();
+        expression.evaluate(&Values::new()).unwrap()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate("1 + 2 * 3"), 7.0);
+        assert_eq!(evaluate("(1 + 2) * 3"), 9.0);
+        assert_eq!(evaluate("10 - 4 - 3"), 3.0);
+        assert_eq!(evaluate("8 / 4 / 2"), 1.0);
+        assert_eq!(evaluate("2 * 3 ^ 2"), 18.0);
+        assert_eq!(evaluate("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(evaluate("-2 ^ 2"), -4.0);
+        assert_eq!(evaluate("2 ^ -1"), 0.5);
+        assert_eq!(evaluate("1 - -1"), 2.0);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(evaluate("1e-5"), 1e-5);
+        assert_eq!(evaluate("2.5E+3"), 2500.0);
+        assert_eq!(evaluate("1e3-1"), 999.0);
+        assert_eq!(evaluate(".5"), 0.5);
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(evaluate("mean(1, 2, 6)"), 3.0);
+        assert_eq!(evaluate("min(3, 1, 2)"), 1.0);
+        assert_eq!(evaluate("max(3, 1, 2)"), 3.0);
+        assert_eq!(evaluate("sum(1, 2, 3)"), 6.0);
+        assert_eq!(evaluate("abs(-2)"), 2.0);
+        assert_eq!(evaluate("sqrt(16)"), 4.0);
+        assert!((evaluate("ln(exp(2))") - 2.0).abs() < 1e-12);
+        assert!((evaluate("log10(1000)") - 3.0).abs() < 1e-12);
+        assert_eq!(evaluate("max(1, 2) * 2"), 4.0);
+    }
+
+    #[test]
+    fn readings() {
+        let expression = "temperature.28ff01 - temperature.28ff02"
+            .parse::<Expression>()
+            .unwrap();
+        assert_eq!(expression.sensors(), BTreeSet::from([Sensor::Temperature]));
+        let mut values = Values::new();
+        values.insert((Sensor::Temperature, 0x28ff01), (Local::now(), 30.0));
+        assert_eq!(expression.evaluate(&values), None);
+        values.insert((Sensor::Temperature, 0x28ff02), (Local::now(), 25.0));
+        assert_eq!(expression.evaluate(&values), Some(5.0));
+    }
+
+    #[test]
+    fn errors() {
+        for source in [
+            "",
+            "1 +",
+            "(1",
+            "1)",
+            "2 3",
+            "1e",
+            "1..2",
+            "foo(1)",
+            "mean()",
+            "abs(1, 2)",
+            "sqrt",
+            "pressure.1",
+            "temperature.xyz",
+        ] {
+            assert!(source.parse::<Expression>().is_err(), "`{source}`");
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use super::{DERIVED_COLUMN, DERIVED_FOLDER, Message};
+use crate::{
+    logger::{Log, Policy},
+    settings::Derived,
+};
+use anyhow::Result;
+use arrow::{
+    array::{Float64Array, RecordBatch, TimestampMillisecondArray, UInt64Array},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::instrument;
+
+/// Writes the derived readings in the logger column order
+#[instrument(err, skip(receiver))]
+pub(super) async fn run(settings: &Derived, receiver: mpsc::Receiver<Message>) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("Identifier", DataType::UInt64, false),
+        Field::new(
+            "Timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            false,
+        ),
+        Field::new(DERIVED_COLUMN, DataType::Float64, false),
+    ]));
+    let policy = Policy {
+        flush: settings.flush,
+        finish: settings.finish,
+    };
+    Log::new(DERIVED_FOLDER, schema, policy)
+        .run(receiver, |schema, message| {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(UInt64Array::from(vec![message.identifier])),
+                    Arc::new(TimestampMillisecondArray::from(vec![
+                        message.date_time.timestamp_millis(),
+                    ])),
+                    Arc::new(Float64Array::from(vec![message.value])),
+                ],
+            )?;
+            Ok((message.date_time, batch))
+        })
+        .await
+}
@@ -0,0 +1,182 @@
+use self::expression::{Expression, Values};
+use crate::{
+    SETTINGS, metrics,
+    sensor::{Readings, Sensor},
+    settings::Channel,
+    temperature::Message as TemperatureMessage,
+    turbidity::Message as TurbidityMessage,
+};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local, TimeDelta};
+use futures_util::future::join;
+use serde::Serialize;
+use std::{io, sync::LazyLock};
+use tokio::{
+    select,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc,
+    },
+    task::{Builder, JoinHandle},
+    time::{Duration, sleep},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, instrument, warn};
+
+const SLEEP: Duration = Duration::from_secs(10);
+const CHANNEL_BUFFER: usize = 64;
+const CHANNEL_LENGTH: usize = 64;
+pub(crate) const DERIVED_FOLDER: &str = "derived";
+pub(crate) const DERIVED_COLUMN: &str = "Derived";
+
+/// Derived readings sender, published by the MQTT client
+pub(crate) static DERIVED: LazyLock<broadcast::Sender<Message>> =
+    LazyLock::new(|| broadcast::channel(CHANNEL_LENGTH).0);
+
+pub(crate) fn spawn(
+    temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: CancellationToken,
+) -> io::Result<JoinHandle<()>> {
+    Builder::new().name("derived").spawn(Box::pin(async move {
+        loop {
+            // Returns once the log is finished, also when cancelled
+            let _ = run(
+                temperature_receiver.resubscribe(),
+                turbidity_receiver.resubscribe(),
+                &cancellation,
+            )
+            .await;
+            if cancellation.is_cancelled() {
+                warn!("derived cancelled");
+                break;
+            }
+            warn!("loop derived");
+            select! {
+                biased;
+                _ = cancellation.cancelled() => {
+                    warn!("derived cancelled");
+                    break;
+                }
+                _ = sleep(SLEEP) => {},
+            };
+        }
+    }))
+}
+
+#[instrument(err, skip(cancellation))]
+async fn run(
+    temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    let Some(settings) = &SETTINGS.derived else {
+        cancellation.cancelled().await;
+        return Ok(());
+    };
+    let channels = settings
+        .channels
+        .iter()
+        .map(|channel| {
+            let identifier = u64::from_str_radix(&channel.identifier, 16)
+                .map_err(|error| anyhow!("derived `{}` identifier: {error}", channel.name))?;
+            let expression = channel
+                .expression
+                .parse::<Expression>()
+                .map_err(|error| anyhow!("derived `{}` expression: {error}", channel.name))?;
+            Ok((channel, identifier, expression))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let (sender, receiver) = mpsc::channel(CHANNEL_BUFFER);
+    // The log finishes its file once the channels stop and drop the sender
+    let derive = async {
+        select! {
+            biased;
+            _ = cancellation.cancelled() => Ok(()),
+            result = derive(&channels, temperature_receiver, turbidity_receiver, sender) => result,
+        }
+    };
+    let (derive, log) = join(derive, log::run(settings, receiver)).await;
+    derive?;
+    log
+}
+
+/// Evaluates the channels reading a sensor on each of its messages
+async fn derive(
+    channels: &[(&Channel, u64, Expression)],
+    mut temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    mut turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    sender: mpsc::Sender<Message>,
+) -> Result<()> {
+    let mut values = Values::new();
+    loop {
+        let (sensor, date_time) = select! {
+            message = temperature_receiver.recv() => match message {
+                Ok(message) => update(&mut values, &message),
+                Err(error @ RecvError::Lagged(_)) => {
+                    warn!(%error);
+                    metrics::lagged("derived temperature");
+                    continue;
+                }
+                Err(error) => Err(error)?,
+            },
+            message = turbidity_receiver.recv() => match message {
+                Ok(message) => update(&mut values, &message),
+                Err(error @ RecvError::Lagged(_)) => {
+                    warn!(%error);
+                    metrics::lagged("derived turbidity");
+                    continue;
+                }
+                Err(error) => Err(error)?,
+            },
+        };
+        for (channel, identifier, expression) in channels {
+            if !expression.sensors().contains(&sensor) {
+                continue;
+            }
+            let Some(value) = expression
+                .evaluate(&values)
+                .filter(|value| value.is_finite())
+            else {
+                continue;
+            };
+            let message = Message {
+                identifier: *identifier,
+                name: channel.name.clone(),
+                date_time,
+                value,
+                unit: channel.unit.clone(),
+            };
+            debug!(?message);
+            // No MQTT client is not an error
+            let _ = DERIVED.send(message.clone());
+            sender.send(message).await?;
+        }
+    }
+}
+
+/// Keeps the readings of a message, and forgets the readings older than two
+/// intervals of their sensor, as a probe may stop reporting
+fn update<T: Readings>(values: &mut Values, message: &T) -> (Sensor, DateTime<Local>) {
+    let date_time = message.date_time();
+    for (identifier, value) in message.readings() {
+        values.insert((T::SENSOR, identifier), (date_time, value));
+    }
+    values.retain(|&(sensor, _), &mut (read, _)| {
+        date_time - read <= TimeDelta::seconds(2 * sensor.settings().interval as i64)
+    });
+    (T::SENSOR, date_time)
+}
+
+/// Derived reading
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Message {
+    pub(crate) identifier: u64,
+    pub(crate) name: String,
+    pub(crate) date_time: DateTime<Local>,
+    pub(crate) value: f64,
+    pub(crate) unit: Option<String>,
+}
+
+mod expression;
+mod log;
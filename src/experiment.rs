@@ -0,0 +1,180 @@
+use crate::SETTINGS;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{create_dir_all, read, rename, write},
+    path::PathBuf,
+    sync::{LazyLock, Mutex, PoisonError},
+};
+use tracing::{info, warn};
+
+const REGISTRY: &str = "experiments.json";
+
+/// Experiment registry, persisted in the output folder
+pub(crate) static EXPERIMENTS: LazyLock<Experiments> = LazyLock::new(Experiments::load);
+
+/// Experiments, running and stopped
+pub(crate) struct Experiments {
+    experiments: Mutex<Vec<Experiment>>,
+}
+
+impl Experiments {
+    fn load() -> Self {
+        let experiments = match read(path()) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|error| {
+                warn!(%error);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            experiments: Mutex::new(experiments),
+        }
+    }
+
+    pub(crate) fn list(&self) -> Vec<Experiment> {
+        self.experiments
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub(crate) fn start(&self, start: Start) -> Result<Experiment> {
+        for identifier in &start.identifiers {
+            u64::from_str_radix(identifier, 16)?;
+        }
+        let mut experiments = self
+            .experiments
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if experiments
+            .iter()
+            .any(|experiment| experiment.name == start.name)
+        {
+            bail!("experiment `{}` already exists", start.name);
+        }
+        let experiment = Experiment {
+            name: start.name,
+            strain: start.strain,
+            reactor: start.reactor,
+            notes: start.notes,
+            identifiers: start.identifiers,
+            start: Local::now(),
+            stop: None,
+        };
+        info!(?experiment);
+        experiments.push(experiment.clone());
+        save(&experiments)?;
+        Ok(experiment)
+    }
+
+    pub(crate) fn stop(&self, name: &str) -> Result<Experiment> {
+        let mut experiments = self
+            .experiments
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(experiment) = experiments
+            .iter_mut()
+            .find(|experiment| experiment.name == name)
+        else {
+            bail!("unknown experiment `{name}`");
+        };
+        if experiment.stop.is_some() {
+            bail!("experiment `{name}` already stopped");
+        }
+        experiment.stop = Some(Local::now());
+        let experiment = experiment.clone();
+        info!(?experiment);
+        save(&experiments)?;
+        Ok(experiment)
+    }
+
+    /// Start and stop of an experiment, now while running
+    pub(crate) fn span(&self, name: &str) -> Result<(DateTime<Local>, DateTime<Local>)> {
+        let experiments = self
+            .experiments
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(experiment) = experiments
+            .iter()
+            .find(|experiment| experiment.name == name)
+        else {
+            bail!("unknown experiment `{name}`");
+        };
+        Ok((experiment.start, experiment.stop.unwrap_or_else(Local::now)))
+    }
+
+    /// Running experiment of each reading, the earliest started one if several
+    pub(crate) fn tags(
+        &self,
+        identifiers: &[u64],
+        date_time: DateTime<Local>,
+    ) -> Vec<Option<String>> {
+        let experiments = self
+            .experiments
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        identifiers
+            .iter()
+            .map(|&identifier| {
+                experiments
+                    .iter()
+                    .find(|experiment| experiment.tags(identifier, date_time))
+                    .map(|experiment| experiment.name.clone())
+            })
+            .collect()
+    }
+}
+
+/// Experiment
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Experiment {
+    pub(crate) name: String,
+    pub(crate) strain: Option<String>,
+    pub(crate) reactor: Option<String>,
+    pub(crate) notes: Option<String>,
+    /// Hexadecimal sensor identifiers, every sensor if empty
+    pub(crate) identifiers: Vec<String>,
+    pub(crate) start: DateTime<Local>,
+    pub(crate) stop: Option<DateTime<Local>>,
+}
+
+impl Experiment {
+    fn tags(&self, identifier: u64, date_time: DateTime<Local>) -> bool {
+        self.start <= date_time
+            && self.stop.is_none_or(|stop| date_time < stop)
+            && (self.identifiers.is_empty()
+                || self.identifiers.iter().any(|hex| {
+                    u64::from_str_radix(hex, 16).is_ok_and(|expected| expected == identifier)
+                }))
+    }
+}
+
+/// Experiment start command
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Start {
+    pub(crate) name: String,
+    pub(crate) strain: Option<String>,
+    pub(crate) reactor: Option<String>,
+    pub(crate) notes: Option<String>,
+    /// Hexadecimal sensor identifiers, every sensor unless given
+    #[serde(default)]
+    pub(crate) identifiers: Vec<String>,
+}
+
+fn path() -> PathBuf {
+    PathBuf::from(&SETTINGS.output).join(REGISTRY)
+}
+
+/// Replaces the registry file
+fn save(experiments: &[Experiment]) -> Result<()> {
+    let path = path();
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    let temporary = path.with_extension("json.tmp");
+    write(&temporary, serde_json::to_vec_pretty(experiments)?)?;
+    rename(temporary, path)?;
+    Ok(())
+}
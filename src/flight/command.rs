@@ -0,0 +1,103 @@
+use crate::{
+    annotation::ANNOTATIONS,
+    archive::{self, Query},
+    sensor::Sensor,
+    temperature::Message as TemperatureMessage,
+    turbidity::Message as TurbidityMessage,
+};
+use anyhow::{Result, bail};
+use arrow::{array::RecordBatch, datatypes::SchemaRef};
+use arrow_flight::{FlightDescriptor, flight_descriptor::DescriptorType};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde::{Deserialize, Serialize};
+use std::{fs::File, path::PathBuf};
+
+/// Ticket and descriptor command, JSON encoded
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum Command {
+    /// Archive query
+    Archive(Query),
+    /// Finished log file
+    File { sensor: Sensor, name: String },
+    /// Live readings
+    Live(Sensor),
+}
+
+impl Command {
+    /// Command of a descriptor, paths are `[sensor]` for live readings and
+    /// `[sensor, file]` for log files
+    pub(super) fn new(descriptor: &FlightDescriptor) -> Result<Self> {
+        Ok(match descriptor.r#type() {
+            DescriptorType::Cmd => serde_json::from_slice(&descriptor.cmd)?,
+            DescriptorType::Path => match &descriptor.path[..] {
+                [sensor] => Self::Live(sensor.parse()?),
+                [sensor, name] => Self::File {
+                    sensor: sensor.parse()?,
+                    name: name.clone(),
+                },
+                path => bail!("unexpected path {path:?}"),
+            },
+            DescriptorType::Unknown => bail!("unknown descriptor type"),
+        })
+    }
+
+    /// Schema and total records, -1 if unknown, blocking
+    pub(super) fn info(&self) -> Result<(SchemaRef, i64)> {
+        match self {
+            // Counting the rows would scan the whole query
+            Self::Archive(query) => {
+                let (schema, _) = archive::batches(archive::empty(query)?)?;
+                Ok((schema, -1))
+            }
+            &Self::File { sensor, ref name } => {
+                let builder =
+                    ParquetRecordBatchReaderBuilder::try_new(File::open(path(sensor, name)?)?)?;
+                let rows = builder.metadata().file_metadata().num_rows();
+                Ok((builder.schema().clone(), rows))
+            }
+            Self::Live(Sensor::Temperature) => {
+                Ok((TemperatureMessage::default().batch()?.schema(), -1))
+            }
+            Self::Live(Sensor::Turbidity) => {
+                Ok((TurbidityMessage::default().batch()?.schema(), -1))
+            }
+        }
+    }
+
+    /// JSON annotations of an archive query if requested, sent as the
+    /// application metadata of the schema message, blocking
+    pub(super) fn annotations(&self) -> Result<Vec<u8>> {
+        match self {
+            Self::Archive(query) if query.annotations => {
+                Ok(serde_json::to_vec(&ANNOTATIONS.concerning(query)?)?)
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Archived record batches, blocking
+    pub(super) fn read(&self) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+        match self {
+            Self::Archive(query) => archive::batches(archive::scan(query)?),
+            &Self::File { sensor, ref name } => {
+                let builder =
+                    ParquetRecordBatchReaderBuilder::try_new(File::open(path(sensor, name)?)?)?;
+                let schema = builder.schema().clone();
+                Ok((schema, builder.build()?.collect::<Result<_, _>>()?))
+            }
+            Self::Live(_) => bail!("live readings are not archived"),
+        }
+    }
+}
+
+/// Finished log file path, only files listed by the archive are served
+fn path(sensor: Sensor, name: &str) -> Result<PathBuf> {
+    match archive::logs(sensor)?
+        .into_iter()
+        .find(|(_, path)| path.file_name().is_some_and(|file_name| file_name == name))
+    {
+        Some((_, path)) => Ok(path),
+        None => bail!("unknown log file `{name}`"),
+    }
+}
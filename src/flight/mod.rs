@@ -0,0 +1,316 @@
+use self::command::Command;
+use crate::{
+    SETTINGS, archive, metrics, sensor::Sensor, temperature::Message as TemperatureMessage,
+    turbidity::Message as TurbidityMessage,
+};
+use anyhow::Result;
+use arrow::{array::RecordBatch, ipc::writer::IpcWriteOptions};
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaAsIpc, SchemaResult, Ticket,
+    encode::FlightDataEncoderBuilder,
+    error::FlightError,
+    flight_service_server::{FlightService, FlightServiceServer},
+};
+use futures_util::{
+    Stream, StreamExt as _, TryStreamExt as _,
+    stream::{iter, unfold},
+};
+use std::{future::pending, io, pin::Pin};
+use tokio::{
+    select,
+    sync::broadcast::{self, error::RecvError},
+    task::{Builder, JoinHandle, spawn_blocking},
+    time::{Duration, sleep},
+};
+use tokio_util::sync::CancellationToken;
+use tonic::{Request, Response, Status, Streaming, async_trait, transport::Server};
+use tracing::{info, instrument, warn};
+
+const SLEEP: Duration = Duration::from_secs(10);
+/// Messages buffered per live stream before the slow ones start dropping
+const STREAM_CAPACITY: usize = 64;
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+pub(crate) fn spawn(
+    temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: CancellationToken,
+) -> io::Result<JoinHandle<()>> {
+    Builder::new().name("flight").spawn(Box::pin(async move {
+        loop {
+            select! {
+                biased;
+                _ = cancellation.cancelled() => {
+                    warn!("flight cancelled");
+                    break;
+                }
+                _ = run(temperature_receiver.resubscribe(), turbidity_receiver.resubscribe(), cancellation.clone()) => {},
+            };
+            warn!("loop flight");
+            sleep(SLEEP).await;
+        }
+    }))
+}
+
+#[instrument(err)]
+async fn run(
+    temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: CancellationToken,
+) -> Result<()> {
+    let Some(settings) = &SETTINGS.flight else {
+        return pending().await;
+    };
+    // Stops live streams whenever the server stops
+    let shutdown = cancellation.child_token();
+    let _guard = shutdown.clone().drop_guard();
+    let (temperature, _) = broadcast::channel(STREAM_CAPACITY);
+    let (turbidity, _) = broadcast::channel(STREAM_CAPACITY);
+    let flight = Flight {
+        temperature: temperature.clone(),
+        turbidity: turbidity.clone(),
+        shutdown: shutdown.clone(),
+    };
+    info!("Flight: {}", settings.address);
+    let serve = Server::builder()
+        .add_service(FlightServiceServer::new(flight))
+        .serve_with_shutdown(settings.address, shutdown.clone().cancelled_owned());
+    select! {
+        result = serve => result?,
+        result = forward(temperature_receiver, temperature) => result?,
+        result = forward(turbidity_receiver, turbidity) => result?,
+    }
+    Ok(())
+}
+
+/// Fans messages out to the live streams
+async fn forward<T: Clone>(
+    mut receiver: broadcast::Receiver<T>,
+    sender: broadcast::Sender<T>,
+) -> Result<()> {
+    loop {
+        match receiver.recv().await {
+            Ok(message) => {
+                // No live streams is not an error
+                let _ = sender.send(message);
+            }
+            Err(error @ RecvError::Lagged(_)) => {
+                warn!(%error);
+                metrics::lagged("flight");
+            }
+            Err(error) => Err(error)?,
+        }
+    }
+}
+
+/// Flight handlers
+struct Flight {
+    temperature: broadcast::Sender<TemperatureMessage>,
+    turbidity: broadcast::Sender<TurbidityMessage>,
+    shutdown: CancellationToken,
+}
+
+#[async_trait]
+impl FlightService for Flight {
+    type HandshakeStream = ResponseStream<HandshakeResponse>;
+    type ListFlightsStream = ResponseStream<FlightInfo>;
+    type DoGetStream = ResponseStream<FlightData>;
+    type DoPutStream = ResponseStream<PutResult>;
+    type DoExchangeStream = ResponseStream<FlightData>;
+    type DoActionStream = ResponseStream<arrow_flight::Result>;
+    type ListActionsStream = ResponseStream<ActionType>;
+
+    async fn handshake(
+        &self,
+        _: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("handshake"))
+    }
+
+    /// Live readings of every sensor followed by its finished log files
+    async fn list_flights(
+        &self,
+        _: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        let infos = spawn_blocking(|| {
+            let mut infos = Vec::new();
+            for sensor in Sensor::ALL {
+                infos.push(info(Command::Live(sensor), None)?);
+                for (_, path) in archive::logs(sensor)? {
+                    if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                        let name = name.to_owned();
+                        infos.push(info(Command::File { sensor, name }, None)?);
+                    }
+                }
+            }
+            anyhow::Ok(infos)
+        })
+        .await
+        .map_err(internal)?
+        .map_err(internal)?;
+        Ok(Response::new(Box::pin(iter(infos.into_iter().map(Ok)))))
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let descriptor = request.into_inner();
+        let command = Command::new(&descriptor).map_err(invalid_argument)?;
+        let info = spawn_blocking(|| info(command, Some(descriptor)))
+            .await
+            .map_err(internal)?
+            .map_err(internal)?;
+        Ok(Response::new(info))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _: Request<FlightDescriptor>,
+    ) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("poll_flight_info"))
+    }
+
+    async fn get_schema(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        let command = Command::new(request.get_ref()).map_err(invalid_argument)?;
+        let (schema, _) = spawn_blocking(move || command.info())
+            .await
+            .map_err(internal)?
+            .map_err(internal)?;
+        let schema = SchemaAsIpc::new(&schema, &IpcWriteOptions::default())
+            .try_into()
+            .map_err(internal)?;
+        Ok(Response::new(schema))
+    }
+
+    /// Archived data, or live readings until the client or the server stops
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        let command = serde_json::from_slice::<Command>(&request.get_ref().ticket)
+            .map_err(invalid_argument)?;
+        info!(?command);
+        let (schema, batches, metadata): (
+            _,
+            Pin<Box<dyn Stream<Item = Result<RecordBatch>> + Send>>,
+            _,
+        ) = match command {
+            Command::Live(Sensor::Temperature) => (
+                TemperatureMessage::default()
+                    .batch()
+                    .map_err(internal)?
+                    .schema(),
+                Box::pin(live(
+                    self.temperature.subscribe(),
+                    TemperatureMessage::batch,
+                )),
+                Vec::new(),
+            ),
+            Command::Live(Sensor::Turbidity) => (
+                TurbidityMessage::default()
+                    .batch()
+                    .map_err(internal)?
+                    .schema(),
+                Box::pin(live(self.turbidity.subscribe(), TurbidityMessage::batch)),
+                Vec::new(),
+            ),
+            command => {
+                let ((schema, batches), metadata) =
+                    spawn_blocking(move || anyhow::Ok((command.read()?, command.annotations()?)))
+                        .await
+                        .map_err(internal)?
+                        .map_err(internal)?;
+                (
+                    schema,
+                    Box::pin(iter(batches.into_iter().map(Ok))),
+                    metadata,
+                )
+            }
+        };
+        let batches = batches
+            .take_until(self.shutdown.clone().cancelled_owned())
+            .map_err(|error| FlightError::ExternalError(error.into()));
+        let data = FlightDataEncoderBuilder::new()
+            .with_metadata(metadata.into())
+            .with_schema(schema)
+            .build(batches)
+            .map_err(Status::from);
+        Ok(Response::new(Box::pin(data)))
+    }
+
+    async fn do_put(
+        &self,
+        _: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("do_put"))
+    }
+
+    async fn do_exchange(
+        &self,
+        _: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("do_exchange"))
+    }
+
+    async fn do_action(
+        &self,
+        _: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("do_action"))
+    }
+
+    async fn list_actions(
+        &self,
+        _: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Err(Status::unimplemented("list_actions"))
+    }
+}
+
+/// Flight info with a single endpoint at this server, blocking
+fn info(command: Command, descriptor: Option<FlightDescriptor>) -> Result<FlightInfo> {
+    let (schema, total_records) = command.info()?;
+    let cmd = serde_json::to_vec(&command)?;
+    Ok(FlightInfo::new()
+        .try_with_schema(&schema)?
+        .with_descriptor(descriptor.unwrap_or_else(|| FlightDescriptor::new_cmd(cmd.clone())))
+        .with_endpoint(FlightEndpoint::new().with_ticket(Ticket::new(cmd)))
+        .with_total_records(total_records)
+        .with_total_bytes(-1)
+        .with_ordered(true))
+}
+
+/// Live record batches, a slow client drops the messages it lags behind on
+fn live<T: Clone + Send + 'static>(
+    receiver: broadcast::Receiver<T>,
+    batch: fn(T) -> Result<RecordBatch>,
+) -> impl Stream<Item = Result<RecordBatch>> {
+    unfold(receiver, move |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(message) => return Some((batch(message), receiver)),
+                Err(error @ RecvError::Lagged(_)) => {
+                    warn!(%error);
+                    metrics::lagged("flight");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+fn internal(error: impl ToString) -> Status {
+    Status::internal(error.to_string())
+}
+
+fn invalid_argument(error: impl ToString) -> Status {
+    Status::invalid_argument(error.to_string())
+}
+
+mod command;
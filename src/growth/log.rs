@@ -0,0 +1,54 @@
+use super::Estimate;
+use crate::{
+    logger::{Log, Policy},
+    settings::Growth,
+};
+use anyhow::Result;
+use arrow::{
+    array::{Float64Array, RecordBatch, TimestampMillisecondArray, UInt64Array},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::instrument;
+
+const GROWTH: &str = "growth";
+
+/// Writes the estimates, the doubling time is `null` unless growing
+#[instrument(err, skip(receiver))]
+pub(super) async fn run(settings: &Growth, receiver: mpsc::Receiver<Estimate>) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("Identifier", DataType::UInt64, false),
+        Field::new(
+            "Timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            false,
+        ),
+        Field::new("Rate", DataType::Float64, false),
+        Field::new("DoublingTime", DataType::Float64, true),
+        Field::new("Determination", DataType::Float64, false),
+        Field::new("Count", DataType::UInt64, false),
+    ]));
+    let policy = Policy {
+        flush: settings.flush,
+        finish: settings.finish,
+    };
+    Log::new(GROWTH, schema, policy)
+        .run(receiver, |schema, estimate| {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(UInt64Array::from(vec![estimate.identifier])),
+                    Arc::new(TimestampMillisecondArray::from(vec![
+                        estimate.date_time.timestamp_millis(),
+                    ])),
+                    Arc::new(Float64Array::from(vec![estimate.rate])),
+                    Arc::new(Float64Array::from(vec![estimate.doubling])),
+                    Arc::new(Float64Array::from(vec![estimate.determination])),
+                    Arc::new(UInt64Array::from(vec![estimate.count as u64])),
+                ],
+            )?;
+            Ok((estimate.date_time, batch))
+        })
+        .await
+}
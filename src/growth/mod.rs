@@ -0,0 +1,172 @@
+use self::window::Window;
+use crate::{
+    SETTINGS, metrics,
+    settings::Growth,
+    turbidity::Message as TurbidityMessage,
+    turbidostat::{Dilution, TURBIDOSTATS},
+};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use futures_util::future::join;
+use serde::Serialize;
+use std::{collections::BTreeMap, io, sync::LazyLock};
+use tokio::{
+    select,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc,
+    },
+    task::{Builder, JoinHandle},
+    time::{Duration, interval, sleep},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, instrument, warn};
+
+const SLEEP: Duration = Duration::from_secs(10);
+const CHANNEL_BUFFER: usize = 64;
+const CHANNEL_LENGTH: usize = 64;
+
+/// Estimates sender, published by the MQTT client
+pub(crate) static ESTIMATES: LazyLock<broadcast::Sender<Estimate>> =
+    LazyLock::new(|| broadcast::channel(CHANNEL_LENGTH).0);
+
+pub(crate) fn spawn(
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: CancellationToken,
+) -> io::Result<JoinHandle<()>> {
+    Builder::new().name("growth").spawn(Box::pin(async move {
+        loop {
+            // Returns once the log is finished, also when cancelled
+            let _ = run(turbidity_receiver.resubscribe(), &cancellation).await;
+            if cancellation.is_cancelled() {
+                warn!("growth cancelled");
+                break;
+            }
+            warn!("loop growth");
+            select! {
+                biased;
+                _ = cancellation.cancelled() => {
+                    warn!("growth cancelled");
+                    break;
+                }
+                _ = sleep(SLEEP) => {},
+            };
+        }
+    }))
+}
+
+#[instrument(err, skip(cancellation))]
+async fn run(
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    let Some(settings) = &SETTINGS.growth else {
+        cancellation.cancelled().await;
+        return Ok(());
+    };
+    let (sender, receiver) = mpsc::channel(CHANNEL_BUFFER);
+    // The log finishes its file once the estimator stops and drops the sender
+    let estimate = async {
+        select! {
+            biased;
+            _ = cancellation.cancelled() => Ok(()),
+            result = estimate(settings, turbidity_receiver, TURBIDOSTATS.subscribe(), sender) => result,
+        }
+    };
+    let (estimate, log) = join(estimate, log::run(settings, receiver)).await;
+    estimate?;
+    log
+}
+
+/// Estimates the growth rate of every turbidity identifier at each interval
+/// with a new reading
+async fn estimate(
+    settings: &Growth,
+    mut turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    mut dilution_receiver: broadcast::Receiver<Dilution>,
+    sender: mpsc::Sender<Estimate>,
+) -> Result<()> {
+    let window = Duration::from_secs(settings.window);
+    let mut windows = BTreeMap::<u64, Window>::new();
+    // Latest reading of the previous estimates
+    let mut estimated = BTreeMap::<u64, DateTime<Local>>::new();
+    let mut interval = interval(Duration::from_secs(settings.interval));
+    loop {
+        select! {
+            message = turbidity_receiver.recv() => match message {
+                Ok(message) => windows
+                    .entry(message.identifier)
+                    .or_default()
+                    .push(message.date_time, message.value as _),
+                Err(error @ RecvError::Lagged(_)) => {
+                    warn!(%error);
+                    metrics::lagged("growth");
+                }
+                Err(error) => Err(error)?,
+            },
+            dilution = dilution_receiver.recv() => match dilution {
+                Ok(dilution) => {
+                    let identifier = diluted(&dilution)?;
+                    for (&key, window) in &mut windows {
+                        if identifier.is_none_or(|identifier| identifier == key) {
+                            window.dilute(dilution.fraction);
+                        }
+                    }
+                }
+                Err(error @ RecvError::Lagged(_)) => {
+                    warn!(%error);
+                    metrics::lagged("growth dilution");
+                }
+                Err(error) => Err(error)?,
+            },
+            _ = interval.tick() => {
+                let since = Local::now() - window;
+                for (&identifier, window) in &mut windows {
+                    window.expire(since);
+                    let Some(estimate) = window.estimate(identifier, settings.minimum) else {
+                        continue;
+                    };
+                    if estimated.insert(identifier, estimate.date_time) == Some(estimate.date_time) {
+                        continue;
+                    }
+                    debug!(?estimate);
+                    // No MQTT client is not an error
+                    let _ = ESTIMATES.send(estimate.clone());
+                    sender.send(estimate).await?;
+                }
+            }
+        }
+    }
+}
+
+/// Turbidity identifier of a dilution, any when the turbidostat has none
+fn diluted(dilution: &Dilution) -> Result<Option<u64>> {
+    let identifier = SETTINGS
+        .dilution
+        .turbidostats
+        .iter()
+        .find(|turbidostat| turbidostat.name == dilution.turbidostat)
+        .and_then(|turbidostat| turbidostat.identifier.as_deref());
+    Ok(identifier
+        .map(|identifier| u64::from_str_radix(identifier, 16))
+        .transpose()?)
+}
+
+/// Growth rate estimate
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Estimate {
+    pub(crate) identifier: u64,
+    pub(crate) name: Option<String>,
+    /// Latest reading of the window
+    pub(crate) date_time: DateTime<Local>,
+    /// Specific growth rate per hour
+    pub(crate) rate: f64,
+    /// Doubling time in hours, growing cultures only
+    pub(crate) doubling: Option<f64>,
+    /// Coefficient of determination of the regression
+    pub(crate) determination: f64,
+    pub(crate) count: usize,
+}
+
+mod log;
+mod window;
@@ -0,0 +1,169 @@
+use super::Estimate;
+use crate::SETTINGS;
+use chrono::{DateTime, Local};
+use std::{collections::VecDeque, f64::consts::LN_2};
+
+/// Sliding window of the log turbidity, corrected for the dilutions from their
+/// start so the regression sees an undiluted culture
+#[derive(Debug, Default)]
+pub(super) struct Window {
+    readings: VecDeque<(DateTime<Local>, f64)>,
+    /// Sum of the logarithms of the dilution factors
+    offset: f64,
+}
+
+impl Window {
+    /// Pushes a reading, the non-positive ones have no logarithm
+    pub(super) fn push(&mut self, date_time: DateTime<Local>, value: f64) {
+        if value > 0.0 {
+            self.readings
+                .push_back((date_time, value.ln() + self.offset));
+        }
+    }
+
+    pub(super) fn dilute(&mut self, fraction: f64) {
+        if (0.0..1.0).contains(&fraction) {
+            self.offset -= (1.0 - fraction).ln();
+        }
+    }
+
+    pub(super) fn expire(&mut self, since: DateTime<Local>) {
+        while self
+            .readings
+            .front()
+            .is_some_and(|&(date_time, _)| date_time < since)
+        {
+            self.readings.pop_front();
+        }
+    }
+
+    /// Least squares slope of the log turbidity over hours
+    pub(super) fn estimate(&self, identifier: u64, minimum: usize) -> Option<Estimate> {
+        let count = self.readings.len();
+        if count < minimum.max(2) {
+            return None;
+        }
+        // From the first reading, so a constant turbidity has no rounding
+        let (first, origin) = self.readings[0];
+        let (last, _) = self.readings[count - 1];
+        let hours = |date_time: DateTime<Local>| {
+            (date_time - first).num_milliseconds() as f64 / 3_600_000.0
+        };
+        let (sum_x, sum_y) = self
+            .readings
+            .iter()
+            .fold((0.0, 0.0), |(sum_x, sum_y), &(date_time, y)| {
+                (sum_x + hours(date_time), sum_y + y - origin)
+            });
+        let (mean_x, mean_y) = (sum_x / count as f64, sum_y / count as f64);
+        let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+        for &(date_time, y) in &self.readings {
+            let (dx, dy) = (hours(date_time) - mean_x, y - origin - mean_y);
+            sxx += dx * dx;
+            sxy += dx * dy;
+            syy += dy * dy;
+        }
+        if sxx == 0.0 {
+            return None;
+        }
+        let rate = sxy / sxx;
+        Some(Estimate {
+            identifier,
+            name: SETTINGS.turbidity.name(identifier).map(ToOwned::to_owned),
+            date_time: last,
+            rate,
+            doubling: (rate > 0.0).then(|| LN_2 / rate),
+            // A constant turbidity fits exactly
+            determination: if syy > 0.0 {
+                sxy * sxy / (sxx * syy)
+            } else {
+                1.0
+            },
+            count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, TimeZone as _};
+
+    fn at(minutes: i64) -> DateTime<Local> {
+        Local.timestamp_opt(1_700_000_000, 0).unwrap() + TimeDelta::minutes(minutes)
+    }
+
+    /// Turbidity growing at a rate per hour
+    fn grown(rate: f64, minutes: i64) -> f64 {
+        100.0 * (rate * minutes as f64 / 60.0).exp()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn exponential() {
+        let mut window = Window::default();
+        for minutes in (0..=120).step_by(10) {
+            window.push(at(minutes), grown(0.5, minutes));
+        }
+        let estimate = window.estimate(1, 3).unwrap();
+        assert_close(estimate.rate, 0.5);
+        assert_close(estimate.doubling.unwrap(), LN_2 / 0.5);
+        assert_close(estimate.determination, 1.0);
+        assert_eq!((estimate.date_time, estimate.count), (at(120), 13));
+    }
+
+    #[test]
+    fn dilution() {
+        let mut window = Window::default();
+        let mut factor = 1.0;
+        for minutes in (0..=120).step_by(10) {
+            // A quarter of the culture is replaced every half hour
+            if minutes > 0 && minutes % 30 == 0 {
+                window.dilute(0.25);
+                factor *= 0.75;
+            }
+            window.push(at(minutes), factor * grown(0.3, minutes));
+        }
+        let estimate = window.estimate(1, 3).unwrap();
+        assert_close(estimate.rate, 0.3);
+        assert_close(estimate.determination, 1.0);
+        // Out of range fractions are ignored
+        window.dilute(1.0);
+        window.dilute(-0.5);
+        window.push(at(130), factor * grown(0.3, 130));
+        assert_close(window.estimate(1, 3).unwrap().rate, 0.3);
+    }
+
+    #[test]
+    fn constant() {
+        let mut window = Window::default();
+        for minutes in 0..5 {
+            window.push(at(minutes), 50.0);
+        }
+        let estimate = window.estimate(1, 3).unwrap();
+        assert_close(estimate.rate, 0.0);
+        assert_eq!(estimate.doubling, None);
+        assert_close(estimate.determination, 1.0);
+    }
+
+    #[test]
+    fn readings() {
+        let mut window = Window::default();
+        window.push(at(0), 0.0);
+        window.push(at(1), -1.0);
+        window.push(at(2), 10.0);
+        // Below the minimum, and never from a single reading
+        assert!(window.estimate(1, 0).is_none());
+        window.push(at(2), 20.0);
+        // No time span
+        assert!(window.estimate(1, 2).is_none());
+        window.push(at(3), 30.0);
+        assert!(window.estimate(1, 4).is_none());
+        assert_eq!(window.estimate(1, 3).unwrap().count, 3);
+        window.expire(at(3));
+        assert_eq!(window.readings.len(), 1);
+    }
+}
@@ -0,0 +1,132 @@
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    pin::pin,
+    sync::{
+        LazyLock, Mutex, PoisonError,
+        atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+    },
+};
+use tokio::sync::Notify;
+
+pub(crate) static START: LazyLock<DateTime<Local>> = LazyLock::new(Local::now);
+
+pub(crate) static TEMPERATURE: Health = Health::new();
+pub(crate) static TURBIDITY: Health = Health::new();
+
+/// Sensor health, the connection is shared by the probes of the bus
+#[derive(Debug)]
+pub(crate) struct Health {
+    connected: AtomicBool,
+    errors: AtomicU64,
+    timestamp: AtomicI64,
+    stale: AtomicBool,
+    reconnects: AtomicU64,
+    /// Reconnect requested, kept until the acquisition loop takes it
+    reconnect_requested: AtomicBool,
+    reconnect: Notify,
+    probes: Mutex<BTreeMap<u64, Probe>>,
+}
+
+impl Health {
+    const fn new() -> Self {
+        Self {
+            connected: AtomicBool::new(false),
+            errors: AtomicU64::new(0),
+            timestamp: AtomicI64::new(i64::MIN),
+            stale: AtomicBool::new(false),
+            reconnects: AtomicU64::new(0),
+            reconnect_requested: AtomicBool::new(false),
+            reconnect: Notify::const_new(),
+            probes: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub(crate) fn connect(&self) {
+        self.connected.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn disconnect(&self) {
+        self.connected.store(false, Ordering::Relaxed);
+    }
+
+    pub(crate) fn error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn read(&self, date_time: DateTime<Local>, identifiers: &[u64]) {
+        self.timestamp
+            .store(date_time.timestamp_millis(), Ordering::Relaxed);
+        let mut probes = self.probes.lock().unwrap_or_else(PoisonError::into_inner);
+        for &identifier in identifiers {
+            probes.entry(identifier).or_default().last_reading = Some(date_time);
+        }
+    }
+
+    /// Set by the watchdog
+    pub(crate) fn stale(&self, stale: bool) {
+        self.stale.store(stale, Ordering::Relaxed);
+    }
+
+    /// Set by the watchdog, a probe is stale when stuck or missing
+    pub(crate) fn probe_stale(&self, identifier: u64, stale: bool) {
+        let mut probes = self.probes.lock().unwrap_or_else(PoisonError::into_inner);
+        probes.entry(identifier).or_default().stale = stale;
+    }
+
+    /// Requests a Modbus reconnect from the acquisition loop
+    pub(crate) fn request_reconnect(&self) {
+        self.reconnect_requested.store(true, Ordering::Relaxed);
+        self.reconnect.notify_waiters();
+    }
+
+    /// Resolves on a reconnect request, including one made while the
+    /// acquisition loop was not waiting
+    pub(crate) async fn reconnection(&self) {
+        let mut notified = pin!(self.reconnect.notified());
+        notified.as_mut().enable();
+        if !self.reconnect_requested.swap(false, Ordering::Relaxed) {
+            notified.await;
+            self.reconnect_requested.store(false, Ordering::Relaxed);
+        }
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn state(&self) -> State {
+        State {
+            connected: self.connected.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            last_reading: DateTime::from_timestamp_millis(self.timestamp.load(Ordering::Relaxed))
+                .map(Into::into),
+            stale: self.stale.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            probes: self
+                .probes
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .map(|(identifier, probe)| (format!("{identifier:x}"), *probe))
+                .collect(),
+        }
+    }
+}
+
+/// Sensor health state
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct State {
+    pub(crate) connected: bool,
+    pub(crate) errors: u64,
+    pub(crate) last_reading: Option<DateTime<Local>>,
+    pub(crate) stale: bool,
+    pub(crate) reconnects: u64,
+    /// Probes by hexadecimal identifier
+    pub(crate) probes: BTreeMap<String, Probe>,
+}
+
+/// Probe health state
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub(crate) struct Probe {
+    pub(crate) last_reading: Option<DateTime<Local>>,
+    pub(crate) stale: bool,
+}
@@ -0,0 +1,25 @@
+use super::Error;
+use crate::actuator::{ACTUATORS, Request, Source, State};
+use axum::{Json, extract::Path};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Actuator states by name
+pub(super) async fn list() -> Json<BTreeMap<String, State>> {
+    Json(ACTUATORS.states())
+}
+
+pub(super) async fn set(
+    Path(actuator): Path<String>,
+    Json(Value { value }): Json<Value>,
+) -> Result<Json<Request>, Error> {
+    let request = ACTUATORS
+        .set(&actuator, value, Source::Command)
+        .map_err(Error::bad_request)?;
+    Ok(Json(request))
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Value {
+    value: u16,
+}
@@ -0,0 +1,18 @@
+use super::Error;
+use crate::alarm::{ALARMS, Event};
+use axum::{Json, extract::Path};
+
+/// Raised and acknowledged alarms
+pub(super) async fn list() -> Json<Vec<Event>> {
+    Json(ALARMS.active())
+}
+
+pub(super) async fn acknowledge(
+    Path((rule, identifier)): Path<(String, String)>,
+) -> Result<Json<Event>, Error> {
+    let identifier = u64::from_str_radix(&identifier, 16).map_err(Error::bad_request)?;
+    let event = ALARMS
+        .acknowledge(&rule, identifier)
+        .map_err(Error::bad_request)?;
+    Ok(Json(event))
+}
@@ -0,0 +1,25 @@
+use super::Error;
+use crate::annotation::{ANNOTATIONS, Annotate, Annotation, Query as AnnotationsQuery};
+use anyhow::anyhow;
+use axum::{Json, extract::Query};
+use tokio::task::spawn_blocking;
+
+/// Annotations within `from..to`, scoped by the `sensor` and `identifier`
+/// parameters of the data query
+pub(super) async fn list(
+    Query(query): Query<AnnotationsQuery>,
+) -> Result<Json<Vec<Annotation>>, Error> {
+    if query.from > query.to {
+        return Err(Error::bad_request(anyhow!("`from` is after `to`")));
+    }
+    if let Some(identifier) = &query.identifier {
+        u64::from_str_radix(identifier, 16).map_err(Error::bad_request)?;
+    }
+    let annotations = spawn_blocking(move || ANNOTATIONS.query(&query)).await??;
+    Ok(Json(annotations))
+}
+
+pub(super) async fn annotate(Json(annotate): Json<Annotate>) -> Result<Json<Annotation>, Error> {
+    let annotation = ANNOTATIONS.annotate(annotate).map_err(Error::bad_request)?;
+    Ok(Json(annotation))
+}
@@ -0,0 +1,25 @@
+use super::Error;
+use crate::control::{CONTROLLERS, State};
+use axum::{Json, extract::Path};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Controller states by name
+pub(super) async fn list() -> Json<BTreeMap<String, State>> {
+    Json(CONTROLLERS.states())
+}
+
+pub(super) async fn setpoint(
+    Path(controller): Path<String>,
+    Json(Setpoint { setpoint }): Json<Setpoint>,
+) -> Result<Json<State>, Error> {
+    let state = CONTROLLERS
+        .set(&controller, setpoint)
+        .map_err(Error::bad_request)?;
+    Ok(Json(state))
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Setpoint {
+    setpoint: f64,
+}
@@ -0,0 +1,8 @@
+use axum::response::Html;
+
+/// Self-contained page, fed by the history and live stream endpoints
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+pub(super) async fn dashboard() -> Html<&'static str> {
+    Html(DASHBOARD)
+}
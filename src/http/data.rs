@@ -0,0 +1,210 @@
+use super::Error;
+use crate::{
+    annotation::{ANNOTATIONS, Annotation},
+    archive::{self, Aggregation},
+    experiment::EXPERIMENTS,
+    sensor::Sensor,
+};
+use anyhow::{Result, anyhow};
+use axum::{
+    extract::{Path, Query},
+    http::{
+        HeaderMap,
+        header::{ACCEPT, CONTENT_TYPE},
+    },
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Local};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use tokio::task::spawn_blocking;
+use tracing::info;
+
+/// Archived readings, the Parquet scan prunes row groups by the time filter
+pub(super) async fn data(
+    Path(sensor): Path<Sensor>,
+    Query(parameters): Query<Parameters>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    // The experiment span unless given
+    let (from, to) = match &parameters.experiment {
+        Some(experiment) => {
+            let (start, stop) = EXPERIMENTS.span(experiment).map_err(Error::bad_request)?;
+            (
+                parameters.from.unwrap_or(start),
+                parameters.to.unwrap_or(stop),
+            )
+        }
+        None => (
+            parameters
+                .from
+                .ok_or_else(|| Error::bad_request(anyhow!("missing `from`")))?,
+            parameters
+                .to
+                .ok_or_else(|| Error::bad_request(anyhow!("missing `to`")))?,
+        ),
+    };
+    if from > to {
+        return Err(Error::bad_request(anyhow!("`from` is after `to`")));
+    }
+    if let Some(every) = &parameters.every {
+        Duration::try_parse(every).map_err(Error::bad_request)?;
+    }
+    if let Some(identifier) = &parameters.identifier {
+        u64::from_str_radix(identifier, 16).map_err(Error::bad_request)?;
+    }
+    let format = Format::new(&headers);
+    // The other formats have no place for them, `/annotations` serves them
+    if parameters.annotations && !matches!(format, Format::Json) {
+        return Err(Error::bad_request(anyhow!(
+            "annotations are only included in JSON"
+        )));
+    }
+    let query = archive::Query {
+        sensor,
+        identifier: parameters.identifier,
+        from,
+        to,
+        every: parameters.every,
+        agg: parameters.agg,
+        experiment: parameters.experiment,
+        annotations: parameters.annotations,
+    };
+    info!(?query);
+    let bytes = spawn_blocking(move || {
+        let bytes = format.write(archive::scan(&query)?)?;
+        if !query.annotations {
+            return Ok(bytes);
+        }
+        let annotated = Annotated {
+            data: serde_json::from_slice(&bytes)?,
+            annotations: ANNOTATIONS.concerning(&query)?,
+        };
+        anyhow::Ok(serde_json::to_vec(&annotated)?)
+    })
+    .await??;
+    Ok(([(CONTENT_TYPE, format.content_type())], bytes).into_response())
+}
+
+/// Archived derived readings, the derived channels are not sensors
+pub(super) async fn derived(
+    Query(parameters): Query<DerivedParameters>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    if parameters.from > parameters.to {
+        return Err(Error::bad_request(anyhow!("`from` is after `to`")));
+    }
+    if let Some(every) = &parameters.every {
+        Duration::try_parse(every).map_err(Error::bad_request)?;
+    }
+    if let Some(identifier) = &parameters.identifier {
+        u64::from_str_radix(identifier, 16).map_err(Error::bad_request)?;
+    }
+    let format = Format::new(&headers);
+    let query = archive::Derived {
+        identifier: parameters.identifier,
+        from: parameters.from,
+        to: parameters.to,
+        every: parameters.every,
+        agg: parameters.agg,
+    };
+    info!(?query);
+    let bytes = spawn_blocking(move || format.write(archive::derived(&query)?)).await??;
+    Ok(([(CONTENT_TYPE, format.content_type())], bytes).into_response())
+}
+
+/// Query parameters
+#[derive(Debug, Deserialize)]
+pub(super) struct Parameters {
+    /// Hexadecimal sensor identifier
+    identifier: Option<String>,
+    /// Start of the experiment if given
+    from: Option<DateTime<Local>>,
+    /// Stop of the experiment if given, now while it runs
+    to: Option<DateTime<Local>>,
+    /// Aggregation window (e.g. `1m`, `1h`)
+    every: Option<String>,
+    #[serde(default)]
+    agg: Aggregation,
+    /// Experiment name
+    experiment: Option<String>,
+    /// Includes the annotations, the JSON readings are then under `data`
+    #[serde(default)]
+    annotations: bool,
+}
+
+/// Derived query parameters
+#[derive(Debug, Deserialize)]
+pub(super) struct DerivedParameters {
+    /// Hexadecimal channel identifier
+    identifier: Option<String>,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+    /// Aggregation window (e.g. `1m`, `1h`)
+    every: Option<String>,
+    #[serde(default)]
+    agg: Aggregation,
+}
+
+/// Readings with the annotations concerning them
+#[derive(Serialize)]
+struct Annotated<'a> {
+    data: &'a RawValue,
+    annotations: Vec<Annotation>,
+}
+
+/// Response format
+#[derive(Clone, Copy, Debug)]
+enum Format {
+    Json,
+    Csv,
+    Parquet,
+    Arrow,
+}
+
+impl Format {
+    /// Format from `Accept`, JSON by default
+    fn new(headers: &HeaderMap) -> Self {
+        let accept = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>();
+        [Self::Arrow, Self::Parquet, Self::Csv, Self::Json]
+            .into_iter()
+            .find(|format| {
+                accept
+                    .iter()
+                    .any(|accept| accept.contains(format.content_type()))
+            })
+            .unwrap_or(Self::Json)
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Csv => "text/csv",
+            Self::Parquet => "application/vnd.apache.parquet",
+            Self::Arrow => "application/vnd.apache.arrow.stream",
+        }
+    }
+
+    /// Writes data frame, blocking
+    fn write(self, mut data_frame: DataFrame) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        match self {
+            Self::Json => JsonWriter::new(&mut bytes)
+                .with_json_format(JsonFormat::Json)
+                .finish(&mut data_frame)?,
+            Self::Csv => CsvWriter::new(&mut bytes).finish(&mut data_frame)?,
+            Self::Parquet => {
+                ParquetWriter::new(&mut bytes).finish(&mut data_frame)?;
+            }
+            Self::Arrow => IpcStreamWriter::new(&mut bytes)
+                .with_compat_level(CompatLevel::oldest())
+                .finish(&mut data_frame)?,
+        }
+        Ok(bytes)
+    }
+}
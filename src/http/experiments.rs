@@ -0,0 +1,18 @@
+use super::Error;
+use crate::experiment::{EXPERIMENTS, Experiment, Start};
+use axum::{Json, extract::Path};
+
+/// Running and stopped experiments
+pub(super) async fn list() -> Json<Vec<Experiment>> {
+    Json(EXPERIMENTS.list())
+}
+
+pub(super) async fn start(Json(start): Json<Start>) -> Result<Json<Experiment>, Error> {
+    let experiment = EXPERIMENTS.start(start).map_err(Error::bad_request)?;
+    Ok(Json(experiment))
+}
+
+pub(super) async fn stop(Path(name): Path<String>) -> Result<Json<Experiment>, Error> {
+    let experiment = EXPERIMENTS.stop(&name).map_err(Error::bad_request)?;
+    Ok(Json(experiment))
+}
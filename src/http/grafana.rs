@@ -0,0 +1,279 @@
+//! Grafana JSON datasource protocol, targets are `sensor/identifier`
+
+use super::{Context, Error};
+use crate::{
+    annotation::{self, ANNOTATIONS},
+    archive::{self, Aggregation, TIMESTAMP},
+    sensor::Sensor,
+};
+use anyhow::{Result, anyhow};
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+};
+use chrono::{DateTime, Local};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::task::spawn_blocking;
+use tracing::info;
+
+pub(super) fn router() -> Router<Context> {
+    Router::new()
+        .route("/", get(test))
+        .route("/search", post(search))
+        .route("/metrics", post(metrics))
+        .route("/query", post(query))
+        .route("/annotations", post(annotations))
+}
+
+/// Datasource connection test
+async fn test() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Targets containing the searched text
+async fn search(State(context): State<Context>, Json(search): Json<Search>) -> Json<Vec<Value>> {
+    Json(
+        targets(&context, &search.target)
+            .into_iter()
+            .map(|(text, value)| json!({ "text": text, "value": value }))
+            .collect(),
+    )
+}
+
+/// Targets as metrics of the newer protocol
+async fn metrics(State(context): State<Context>, Json(search): Json<Search>) -> Json<Vec<Value>> {
+    Json(
+        targets(&context, &search.metric)
+            .into_iter()
+            .map(|(label, value)| json!({ "label": label, "value": value, "payloads": [] }))
+            .collect(),
+    )
+}
+
+/// Time series of every visible target, archived readings aggregated by the
+/// interval followed by the latest one not archived yet
+async fn query(
+    State(context): State<Context>,
+    Json(request): Json<QueryRequest>,
+) -> Result<Json<Vec<Series>>, Error> {
+    let Range { from, to } = request.range;
+    if from > to {
+        return Err(Error::bad_request(anyhow!("`from` is after `to`")));
+    }
+    // Widens the interval so the series fit in the maximum data points
+    let span = (to - from).num_milliseconds().max(0) as u64;
+    let interval = request
+        .max_data_points
+        .filter(|&points| points > 0)
+        .map_or(0, |points| span.div_ceil(points))
+        .max(request.interval_ms.unwrap_or_default());
+    let every = (interval > 0).then(|| format!("{interval}ms"));
+    let mut queries = Vec::new();
+    for target in request.targets {
+        let Some(target) = target.target.filter(|_| !target.hide) else {
+            continue;
+        };
+        let (sensor, identifier) = parse(&target).map_err(Error::bad_request)?;
+        let query = archive::Query {
+            sensor,
+            identifier: Some(format!("{identifier:x}")),
+            from,
+            to,
+            every: every.clone(),
+            agg: Aggregation::Mean,
+            experiment: None,
+            annotations: false,
+        };
+        info!(?query);
+        queries.push((target, identifier, query));
+    }
+    let series = spawn_blocking(move || {
+        queries
+            .into_iter()
+            .map(|(target, identifier, query)| {
+                let datapoints = datapoints(&archive::scan(&query)?, query.sensor)?;
+                Ok((query.sensor, identifier, Series { target, datapoints }))
+            })
+            .collect::<Result<Vec<_>>>()
+    })
+    .await??;
+    Ok(Json(
+        series
+            .into_iter()
+            .map(|(sensor, identifier, mut series)| {
+                if let Some((date_time, value)) = latest(&context, sensor, identifier) {
+                    let timestamp = date_time.timestamp_millis();
+                    let archived = series.datapoints.last().map(|&(_, timestamp)| timestamp);
+                    if from <= date_time && date_time <= to && archived < Some(timestamp) {
+                        series.datapoints.push((value, timestamp));
+                    }
+                }
+                series
+            })
+            .collect(),
+    ))
+}
+
+/// Annotations within the range, the query is empty or a `sensor` or
+/// `sensor/identifier` target
+async fn annotations(Json(request): Json<AnnotationsRequest>) -> Result<Json<Vec<Value>>, Error> {
+    let Range { from, to } = request.range;
+    if from > to {
+        return Err(Error::bad_request(anyhow!("`from` is after `to`")));
+    }
+    let target = request
+        .annotation
+        .query
+        .as_deref()
+        .unwrap_or_default()
+        .trim();
+    let (sensor, identifier) = match target.split_once('/') {
+        _ if target.is_empty() => (None, None),
+        Some((sensor, identifier)) => (Some(sensor), Some(identifier.to_owned())),
+        None => (Some(target), None),
+    };
+    let sensor = sensor
+        .map(str::parse)
+        .transpose()
+        .map_err(Error::bad_request)?;
+    let query = annotation::Query {
+        from,
+        to,
+        sensor,
+        identifier,
+    };
+    info!(?query);
+    let annotations = spawn_blocking(move || ANNOTATIONS.query(&query)).await??;
+    Ok(Json(
+        annotations
+            .into_iter()
+            .map(|annotation| {
+                let mut tags = vec![annotation.author.clone()];
+                tags.extend(annotation.sensor.map(|sensor| sensor.to_string()));
+                tags.extend(annotation.identifier.clone());
+                json!({
+                    "annotation": request.annotation,
+                    "time": annotation.date_time.timestamp_millis(),
+                    "title": annotation.author,
+                    "text": annotation.text,
+                    "tags": tags,
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// Targets with their labels, the name if configured
+fn targets(context: &Context, filter: &str) -> Vec<(String, String)> {
+    let mut targets = Vec::new();
+    for sensor in Sensor::ALL {
+        let settings = sensor.settings();
+        for identifier in context.known(sensor) {
+            let target = format!("{sensor}/{identifier:x}");
+            let label = match settings.name(identifier) {
+                Some(name) => format!("{sensor} {name}"),
+                None => target.clone(),
+            };
+            if label.contains(filter) || target.contains(filter) {
+                targets.push((label, target));
+            }
+        }
+    }
+    targets
+}
+
+/// Sensor and identifier of a `sensor/identifier` target
+fn parse(target: &str) -> Result<(Sensor, u64)> {
+    let (sensor, identifier) = target
+        .split_once('/')
+        .ok_or_else(|| anyhow!("unexpected target `{target}`"))?;
+    Ok((sensor.parse()?, u64::from_str_radix(identifier, 16)?))
+}
+
+/// Value and Unix timestamp in milliseconds pairs
+fn datapoints(data_frame: &DataFrame, sensor: Sensor) -> Result<Vec<(f64, i64)>> {
+    let timestamps = data_frame.column(TIMESTAMP)?.cast(&DataType::Int64)?;
+    let values = data_frame
+        .column(sensor.column())?
+        .cast(&DataType::Float64)?;
+    Ok(timestamps
+        .i64()?
+        .into_iter()
+        .zip(values.f64()?)
+        .filter_map(|(timestamp, value)| Some((value?, timestamp?)))
+        .collect())
+}
+
+/// Latest in-memory reading of a sensor
+fn latest(context: &Context, sensor: Sensor, identifier: u64) -> Option<(DateTime<Local>, f64)> {
+    match sensor {
+        Sensor::Temperature => context.temperature.borrow().as_ref().and_then(|message| {
+            let index = message
+                .identifiers
+                .iter()
+                .position(|&id| id == identifier)?;
+            Some((message.date_time, *message.values.get(index)? as _))
+        }),
+        Sensor::Turbidity => context
+            .turbidity
+            .borrow()
+            .filter(|message| message.identifier == identifier)
+            .map(|message| (message.date_time, message.value as _)),
+    }
+}
+
+/// Search request, `target` in the older protocol and `metric` in the newer one
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Search {
+    target: String,
+    metric: String,
+}
+
+/// Query request
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QueryRequest {
+    range: Range,
+    interval_ms: Option<u64>,
+    max_data_points: Option<u64>,
+    targets: Vec<Target>,
+}
+
+/// Annotations request, the annotation is echoed in the response
+#[derive(Debug, Deserialize)]
+struct AnnotationsRequest {
+    range: Range,
+    annotation: AnnotationQuery,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct AnnotationQuery {
+    #[serde(default)]
+    name: String,
+    query: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Range {
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Target {
+    target: Option<String>,
+    #[serde(default)]
+    hide: bool,
+}
+
+/// Time series response
+#[derive(Debug, Serialize)]
+struct Series {
+    target: String,
+    datapoints: Vec<(f64, i64)>,
+}
@@ -0,0 +1,249 @@
+use crate::{
+    SETTINGS, metrics, mqtt::Encoding, sensor::Sensor, temperature::Message as TemperatureMessage,
+    turbidity::Message as TurbidityMessage,
+};
+use anyhow::Result;
+use arrow::array::RecordBatch;
+use axum::{
+    Router,
+    http::{
+        HeaderMap, StatusCode,
+        header::{ACCEPT, CONTENT_TYPE},
+    },
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+};
+use std::{collections::BTreeSet, future::pending, io};
+use tokio::{
+    net::TcpListener,
+    select,
+    sync::{
+        broadcast::{self, error::RecvError},
+        watch,
+    },
+    task::{Builder, JoinHandle},
+    time::{Duration, sleep},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, instrument, warn};
+
+const SLEEP: Duration = Duration::from_secs(10);
+/// Messages buffered per stream client before the slow ones start dropping
+const STREAM_CAPACITY: usize = 64;
+
+pub(crate) fn spawn(
+    temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: CancellationToken,
+) -> io::Result<JoinHandle<()>> {
+    Builder::new().name("http").spawn(Box::pin(async move {
+        loop {
+            select! {
+                biased;
+                _ = cancellation.cancelled() => {
+                    warn!("http cancelled");
+                    break;
+                }
+                _ = run(temperature_receiver.resubscribe(), turbidity_receiver.resubscribe(), cancellation.clone()) => {},
+            };
+            warn!("loop http");
+            sleep(SLEEP).await;
+        }
+    }))
+}
+
+#[instrument(err)]
+async fn run(
+    temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: CancellationToken,
+) -> Result<()> {
+    let Some(settings) = &SETTINGS.http else {
+        return pending().await;
+    };
+    // Stops stream clients whenever the server stops
+    let shutdown = cancellation.child_token();
+    let _guard = shutdown.clone().drop_guard();
+    let (temperature_sender, temperature) = watch::channel(None);
+    let (turbidity_sender, turbidity) = watch::channel(None);
+    let (temperature_stream, _) = broadcast::channel(STREAM_CAPACITY);
+    let (turbidity_stream, _) = broadcast::channel(STREAM_CAPACITY);
+    let router = Router::new()
+        .route("/", get(dashboard::dashboard))
+        .route("/sensors", get(sensors::list))
+        .route("/sensors/{sensor}/latest", get(sensors::latest))
+        .route("/sensors/{sensor}/data", get(data::data))
+        .route("/derived/data", get(data::derived))
+        .route("/status", get(status::status))
+        .route("/config", get(status::config))
+        .route("/alarms", get(alarms::list))
+        .route(
+            "/alarms/{rule}/{identifier}/acknowledge",
+            post(alarms::acknowledge),
+        )
+        .route("/actuators", get(actuators::list))
+        .route("/actuators/{actuator}", put(actuators::set))
+        .route("/control", get(control::list))
+        .route("/turbidostats", get(turbidostats::list))
+        .route(
+            "/experiments",
+            get(experiments::list).post(experiments::start),
+        )
+        .route("/experiments/{name}/stop", post(experiments::stop))
+        .route(
+            "/annotations",
+            get(annotations::list).post(annotations::annotate),
+        )
+        .route("/control/{controller}/setpoint", put(control::setpoint))
+        .route("/metrics", get(status::metrics))
+        .route("/stream/sse", get(stream::sse))
+        .route("/stream/ws", get(stream::ws))
+        .nest("/grafana", grafana::router())
+        .with_state(Context {
+            temperature,
+            turbidity,
+            temperature_stream: temperature_stream.clone(),
+            turbidity_stream: turbidity_stream.clone(),
+            shutdown: shutdown.clone(),
+        });
+    let listener = TcpListener::bind(settings.address).await?;
+    info!("HTTP: {}", settings.address);
+    let serve = axum::serve(listener, router).with_graceful_shutdown(shutdown.cancelled_owned());
+    select! {
+        result = serve => result?,
+        result = forward(temperature_receiver, temperature_sender, temperature_stream) => result?,
+        result = forward(turbidity_receiver, turbidity_sender, turbidity_stream) => result?,
+    }
+    Ok(())
+}
+
+/// Keeps the latest message and fans messages out to the stream clients
+async fn forward<T: Clone>(
+    mut receiver: broadcast::Receiver<T>,
+    latest: watch::Sender<Option<T>>,
+    stream: broadcast::Sender<T>,
+) -> Result<()> {
+    loop {
+        match receiver.recv().await {
+            Ok(message) => {
+                // No stream clients is not an error
+                let _ = stream.send(message.clone());
+                latest.send_replace(Some(message));
+            }
+            Err(error @ RecvError::Lagged(_)) => {
+                warn!(%error);
+                metrics::lagged("http");
+            }
+            Err(error) => Err(error)?,
+        }
+    }
+}
+
+/// Handlers state
+#[derive(Clone)]
+struct Context {
+    temperature: watch::Receiver<Option<TemperatureMessage>>,
+    turbidity: watch::Receiver<Option<TurbidityMessage>>,
+    temperature_stream: broadcast::Sender<TemperatureMessage>,
+    turbidity_stream: broadcast::Sender<TurbidityMessage>,
+    shutdown: CancellationToken,
+}
+
+impl Context {
+    /// Identifiers named in the config or present in the latest message
+    fn known(&self, sensor: Sensor) -> BTreeSet<u64> {
+        let mut known = sensor
+            .settings()
+            .names
+            .keys()
+            .filter_map(|identifier| u64::from_str_radix(identifier, 16).ok())
+            .collect::<BTreeSet<_>>();
+        known.extend(self.identifiers(sensor));
+        known
+    }
+
+    /// Identifiers of the latest message
+    fn identifiers(&self, sensor: Sensor) -> Vec<u64> {
+        match sensor {
+            Sensor::Temperature => self
+                .temperature
+                .borrow()
+                .as_ref()
+                .map(|message| message.identifiers.clone())
+                .unwrap_or_default(),
+            Sensor::Turbidity => self
+                .turbidity
+                .borrow()
+                .iter()
+                .map(|message| message.identifier)
+                .collect(),
+        }
+    }
+}
+
+/// Arrow IPC if acceptable, JSON otherwise
+fn encoding(headers: &HeaderMap) -> Encoding {
+    let arrow = headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.contains(Encoding::Arrow.content_type()));
+    if arrow {
+        Encoding::Arrow
+    } else {
+        Encoding::Json
+    }
+}
+
+fn encode(headers: &HeaderMap, batch: &RecordBatch) -> Result<Response, Error> {
+    let encoding = encoding(headers);
+    Ok((
+        [(CONTENT_TYPE, encoding.content_type())],
+        encoding.encode(batch, None)?,
+    )
+        .into_response())
+}
+
+/// Error response, internal server error unless stated otherwise
+struct Error {
+    status: StatusCode,
+    error: anyhow::Error,
+}
+
+impl Error {
+    fn bad_request(error: impl Into<anyhow::Error>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            error: error.into(),
+        }
+    }
+}
+
+impl<T: Into<anyhow::Error>> From<T> for Error {
+    fn from(value: T) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            error: value.into(),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        warn!(status = %self.status, error = %self.error);
+        (self.status, self.error.to_string()).into_response()
+    }
+}
+
+mod actuators;
+mod alarms;
+mod annotations;
+mod control;
+mod dashboard;
+mod data;
+mod experiments;
+mod grafana;
+mod sensors;
+mod status;
+mod stream;
+mod turbidostats;
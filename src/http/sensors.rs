@@ -0,0 +1,59 @@
+use super::{Context, Error, encode};
+use crate::sensor::Sensor;
+use arrow::array::{ArrayRef, RecordBatch, StringArray, UInt64Array};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+/// Known sensors: named in the config or present in the latest readings
+pub(super) async fn list(
+    State(context): State<Context>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let mut sensors = Vec::new();
+    let mut identifiers = Vec::new();
+    let mut names = Vec::new();
+    let mut units = Vec::new();
+    for sensor in Sensor::ALL {
+        let settings = sensor.settings();
+        for identifier in context.known(sensor) {
+            sensors.push(sensor.name());
+            identifiers.push(identifier);
+            names.push(settings.name(identifier));
+            units.push(sensor.unit());
+        }
+    }
+    let batch = RecordBatch::try_from_iter([
+        ("Sensor", Arc::new(StringArray::from(sensors)) as ArrayRef),
+        ("Identifier", Arc::new(UInt64Array::from(identifiers))),
+        ("Name", Arc::new(StringArray::from(names))),
+        ("Unit", Arc::new(StringArray::from(units))),
+    ])?;
+    encode(&headers, &batch)
+}
+
+/// Latest reading of every sensor of a kind
+pub(super) async fn latest(
+    State(context): State<Context>,
+    Path(sensor): Path<Sensor>,
+    headers: HeaderMap,
+) -> Result<Response, Error> {
+    let batch = match sensor {
+        Sensor::Temperature => {
+            let Some(message) = context.temperature.borrow().clone() else {
+                return Ok(StatusCode::NOT_FOUND.into_response());
+            };
+            message.batch()?
+        }
+        Sensor::Turbidity => {
+            let Some(message) = *context.turbidity.borrow() else {
+                return Ok(StatusCode::NOT_FOUND.into_response());
+            };
+            message.batch()?
+        }
+    };
+    encode(&headers, &batch)
+}
@@ -0,0 +1,64 @@
+use super::Error;
+use crate::{
+    SETTINGS,
+    health::{self, START},
+    logger, metrics,
+    settings::Logger,
+};
+use axum::{
+    Json,
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Sensors health and logger writers state
+pub(super) async fn status() -> Json<Value> {
+    Json(json!({
+        "version": VERSION,
+        "start": *START,
+        "health": {
+            "temperature": health::TEMPERATURE.state(),
+            "turbidity": health::TURBIDITY.state(),
+        },
+        "writers": {
+            "temperature": logger::TEMPERATURE.state(),
+            "turbidity": logger::TURBIDITY.state(),
+        },
+    }))
+}
+
+/// Allow-listed view of the settings, credentials, addresses and paths are
+/// left out
+pub(super) async fn config() -> Json<Value> {
+    Json(json!({
+        "temperature": sensor(&SETTINGS.temperature),
+        "turbidity": sensor(&SETTINGS.turbidity),
+        "mqtt": {
+            "version": SETTINGS.mqtt.version,
+            "status": SETTINGS.mqtt.status,
+        },
+        "alarm": {
+            "rules": SETTINGS.alarm.rules,
+        },
+        "derived": SETTINGS.derived.as_ref().map(|derived| &derived.channels),
+    }))
+}
+
+fn sensor(settings: &Logger) -> Value {
+    json!({
+        "count": settings.count,
+        "interval": settings.interval,
+        "unit": settings.unit,
+        "names": settings.names,
+        "watchdog": settings.watchdog,
+        "rollups": settings.rollups,
+    })
+}
+
+/// Prometheus metrics
+pub(super) async fn metrics() -> Result<Response, Error> {
+    Ok(([(CONTENT_TYPE, metrics::CONTENT_TYPE)], metrics::gather()?).into_response())
+}
@@ -0,0 +1,204 @@
+use super::{Context, Error};
+use crate::{
+    metrics, mqtt::Encoding, sensor::Sensor, temperature::Message as TemperatureMessage,
+    turbidity::Message as TurbidityMessage,
+};
+use anyhow::Result;
+use arrow::{
+    array::{AsArray as _, BooleanArray, RecordBatch},
+    compute::filter_record_batch,
+    datatypes::UInt64Type,
+};
+use axum::{
+    extract::{
+        Query, State, WebSocketUpgrade,
+        ws::{Message, WebSocket},
+    },
+    response::{
+        Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::{Stream, stream::unfold};
+use serde::Deserialize;
+use std::{collections::BTreeSet, future::pending};
+use tokio::{
+    select,
+    sync::broadcast::{self, error::RecvError},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{instrument, warn};
+
+/// Server-Sent Events, one JSON event named after the sensor per message
+pub(super) async fn sse(
+    State(context): State<Context>,
+    Query(parameters): Query<Parameters>,
+) -> Result<Sse<impl Stream<Item = Result<Event>>>, Error> {
+    let subscription = Subscription::new(&context, &parameters)?;
+    let stream = unfold(subscription, |mut subscription| async move {
+        let next = subscription.next().await.transpose()?;
+        let event = next.and_then(|(sensor, batch)| {
+            let data = Encoding::Json.encode(&batch, None)?;
+            Ok(Event::default()
+                .event(sensor.name())
+                .data(str::from_utf8(&data)?))
+        });
+        Some((event, subscription))
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// WebSocket, JSON frames are text, other encodings are binary
+pub(super) async fn ws(
+    State(context): State<Context>,
+    Query(parameters): Query<Parameters>,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, Error> {
+    let subscription = Subscription::new(&context, &parameters)?;
+    let encoding = parameters.encoding;
+    Ok(upgrade.on_upgrade(move |socket| async move {
+        let _ = send(socket, subscription, encoding).await;
+    }))
+}
+
+#[instrument(err, skip(socket, subscription))]
+async fn send(
+    mut socket: WebSocket,
+    mut subscription: Subscription,
+    encoding: Encoding,
+) -> Result<()> {
+    loop {
+        select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => {}
+                Some(Err(error)) => Err(error)?,
+            },
+            next = subscription.next() => {
+                let Some((_, batch)) = next? else {
+                    return Ok(socket.send(Message::Close(None)).await?);
+                };
+                let bytes = encoding.encode(&batch, None)?;
+                let message = match encoding {
+                    Encoding::Json => Message::Text(String::from_utf8(bytes.into())?.into()),
+                    _ => Message::Binary(bytes),
+                };
+                socket.send(message).await?;
+            }
+        }
+    }
+}
+
+/// Stream parameters
+#[derive(Debug, Deserialize)]
+pub(super) struct Parameters {
+    /// Comma separated sensor names, all sensors by default
+    sensors: Option<String>,
+    /// Comma separated hexadecimal sensor identifiers, all identifiers by
+    /// default
+    identifier: Option<String>,
+    /// WebSocket frames encoding
+    #[serde(default = "Parameters::encoding")]
+    encoding: Encoding,
+}
+
+impl Parameters {
+    fn encoding() -> Encoding {
+        Encoding::Json
+    }
+}
+
+/// Subscription of a stream client to the selected sensors, a slow client
+/// drops the messages it lags behind on
+struct Subscription {
+    temperature: Option<broadcast::Receiver<TemperatureMessage>>,
+    turbidity: Option<broadcast::Receiver<TurbidityMessage>>,
+    identifiers: Option<BTreeSet<u64>>,
+    shutdown: CancellationToken,
+}
+
+impl Subscription {
+    fn new(context: &Context, parameters: &Parameters) -> Result<Self, Error> {
+        let sensors = match &parameters.sensors {
+            Some(sensors) => sensors
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<Vec<Sensor>>>()
+                .map_err(Error::bad_request)?,
+            None => Sensor::ALL.to_vec(),
+        };
+        let identifiers = parameters
+            .identifier
+            .as_deref()
+            .map(|identifiers| {
+                identifiers
+                    .split(',')
+                    .map(|identifier| u64::from_str_radix(identifier, 16))
+                    .collect()
+            })
+            .transpose()
+            .map_err(Error::bad_request)?;
+        Ok(Self {
+            temperature: sensors
+                .contains(&Sensor::Temperature)
+                .then(|| context.temperature_stream.subscribe()),
+            turbidity: sensors
+                .contains(&Sensor::Turbidity)
+                .then(|| context.turbidity_stream.subscribe()),
+            identifiers,
+            shutdown: context.shutdown.clone(),
+        })
+    }
+
+    /// Next message with rows of the selected identifiers as record batch,
+    /// `None` once the server stops
+    async fn next(&mut self) -> Result<Option<(Sensor, RecordBatch)>> {
+        loop {
+            let result = select! {
+                _ = self.shutdown.cancelled() => return Ok(None),
+                result = recv(&mut self.temperature) => {
+                    result.map(|message| (Sensor::Temperature, message.batch()))
+                }
+                result = recv(&mut self.turbidity) => {
+                    result.map(|message| (Sensor::Turbidity, message.batch()))
+                }
+            };
+            match result {
+                Ok((sensor, batch)) => {
+                    let batch = self.filter(batch?)?;
+                    if batch.num_rows() > 0 {
+                        return Ok(Some((sensor, batch)));
+                    }
+                }
+                Err(error @ RecvError::Lagged(_)) => {
+                    warn!(%error);
+                    metrics::lagged("http stream");
+                }
+                Err(RecvError::Closed) => return Ok(None),
+            }
+        }
+    }
+
+    /// Rows of the selected identifiers
+    fn filter(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let Some(identifiers) = &self.identifiers else {
+            return Ok(batch);
+        };
+        let predicate = batch
+            .column(0)
+            .as_primitive::<UInt64Type>()
+            .iter()
+            .map(|identifier| {
+                Some(identifier.is_some_and(|identifier| identifiers.contains(&identifier)))
+            })
+            .collect::<BooleanArray>();
+        Ok(filter_record_batch(&batch, &predicate)?)
+    }
+}
+
+async fn recv<T: Clone>(receiver: &mut Option<broadcast::Receiver<T>>) -> Result<T, RecvError> {
+    match receiver {
+        Some(receiver) => receiver.recv().await,
+        None => pending().await,
+    }
+}
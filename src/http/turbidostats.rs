@@ -0,0 +1,8 @@
+use crate::turbidostat::{State, TURBIDOSTATS};
+use axum::Json;
+use std::collections::BTreeMap;
+
+/// Turbidostat states by name
+pub(super) async fn list() -> Json<BTreeMap<String, State>> {
+    Json(TURBIDOSTATS.states())
+}
@@ -0,0 +1,137 @@
+use super::Writer;
+use anyhow::Result;
+use arrow::{array::RecordBatch, datatypes::SchemaRef};
+use chrono::{DateTime, Local, TimeDelta};
+use object_store::{ObjectStore, local::LocalFileSystem};
+use std::sync::Arc;
+use tokio::{
+    select,
+    sync::mpsc,
+    time::{Duration, Instant, sleep_until},
+};
+use tracing::info;
+
+/// Longest time a file stays open, its rows are not readable and are lost on a
+/// restart until it is finished
+const PERIOD: Duration = Duration::from_secs(600);
+
+/// Rows per row group and row groups per file
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Policy {
+    pub(crate) flush: usize,
+    pub(crate) finish: usize,
+}
+
+/// Event log, a file is finished after the policy row groups or the period,
+/// whichever comes first
+pub(crate) struct Log {
+    folder: &'static str,
+    schema: SchemaRef,
+    policy: Policy,
+    store: Arc<dyn ObjectStore>,
+    writer: Option<(Writer, Instant)>,
+    /// Date time naming the latest file
+    named: Option<DateTime<Local>>,
+}
+
+impl Log {
+    pub(crate) fn new(folder: &'static str, schema: SchemaRef, policy: Policy) -> Self {
+        Self {
+            folder,
+            schema,
+            policy,
+            store: Arc::new(LocalFileSystem::new()),
+            writer: None,
+            named: None,
+        }
+    }
+
+    /// Writes the batches of the received rows, the file is finished once the
+    /// senders are dropped
+    pub(crate) async fn run<T>(
+        mut self,
+        mut receiver: mpsc::Receiver<T>,
+        mut batch: impl FnMut(&SchemaRef, T) -> Result<(DateTime<Local>, RecordBatch)>,
+    ) -> Result<()> {
+        loop {
+            let deadline = self.deadline();
+            select! {
+                _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    self.finish().await?;
+                }
+                message = receiver.recv() => {
+                    let Some(message) = message else {
+                        break;
+                    };
+                    let (date_time, batch) = batch(&self.schema, message)?;
+                    self.write(date_time, &batch).await?;
+                }
+            }
+        }
+        self.finish().await?;
+        Ok(())
+    }
+
+    pub(crate) fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    /// Deadline of the open file
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.writer.as_ref().map(|&(_, deadline)| deadline)
+    }
+
+    /// Writes a batch to the open file or to a new one named by the date time,
+    /// a second after the latest one at least as the names have no fractions,
+    /// returns whether the file is finished
+    pub(crate) async fn write(
+        &mut self,
+        date_time: DateTime<Local>,
+        batch: &RecordBatch,
+    ) -> Result<bool> {
+        let (writer, _) = match &mut self.writer {
+            Some(writer) => writer,
+            None => {
+                let date_time = match self.named {
+                    Some(named) if date_time.timestamp() <= named.timestamp() => {
+                        named + TimeDelta::seconds(1)
+                    }
+                    _ => date_time,
+                };
+                let writer = Writer::builder()
+                    .schema(self.schema.clone())
+                    .store(self.store.clone())
+                    .folder(self.folder)
+                    .date_time(date_time)
+                    .build()?;
+                self.named = Some(date_time);
+                self.writer.insert((writer, Instant::now() + PERIOD))
+            }
+        };
+        writer.write(batch).await?;
+        // Check for flush
+        if writer.in_progress_rows() >= self.policy.flush {
+            writer.flush().await?;
+        }
+        // Check for writer
+        if writer.flushed_row_groups().len() >= self.policy.finish {
+            return self.finish().await;
+        }
+        Ok(false)
+    }
+
+    /// Finishes the open file, returns whether there is one
+    pub(crate) async fn finish(&mut self) -> Result<bool> {
+        let Some((mut writer, _)) = self.writer.take() else {
+            return Ok(false);
+        };
+        info!(
+            "Finish {} {}+{}",
+            self.folder,
+            writer.flushed_row_groups().len(),
+            writer.in_progress_rows(),
+        );
+        writer.finish().await?;
+        Ok(true)
+    }
+}
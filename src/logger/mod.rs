@@ -1,4 +1,9 @@
-use self::writer::Writer;
+pub(crate) use self::{
+    log::{Log, Policy},
+    writer::Writer,
+};
+
+use self::writer::Status;
 use crate::{temperature::Message as TemperatureMessage, turbidity::Message as TurbidityMessage};
 use std::io;
 use tokio::{
@@ -9,6 +14,9 @@ use tokio::{
 use tokio_util::sync::CancellationToken;
 use tracing::warn;
 
+pub(crate) static TEMPERATURE: Status = Status::new();
+pub(crate) static TURBIDITY: Status = Status::new();
+
 // https://github.com/apache/arrow-rs/blob/main/parquet/src/bin/parquet-concat.rs
 // https://github.com/apache/arrow-rs/issues/557
 pub(crate) fn spawn(
@@ -16,6 +24,8 @@ pub(crate) fn spawn(
     turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
     cancellation: CancellationToken,
 ) -> io::Result<JoinHandle<()>> {
+    rollup::spawn(temperature_receiver.resubscribe(), cancellation.clone())?;
+    rollup::spawn(turbidity_receiver.resubscribe(), cancellation.clone())?;
     Builder::new().name("logger").spawn(Box::pin(async move {
         loop {
             let temperature =
@@ -35,6 +45,8 @@ pub(crate) fn spawn(
     }))
 }
 
+mod log;
+mod rollup;
 mod temperature;
 mod turbidity;
 mod writer;
@@ -0,0 +1,392 @@
+use super::Writer;
+use crate::{metrics, sensor::Readings, settings::Logger};
+use anyhow::{Result, anyhow, bail};
+use arrow::{
+    array::{Float64Array, RecordBatch, TimestampMillisecondArray, UInt64Array},
+    datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+};
+use chrono::{DateTime, Local, TimeZone as _};
+use object_store::{ObjectStore, local::LocalFileSystem};
+use std::{collections::BTreeMap, io, mem::take, sync::Arc};
+use tokio::{
+    select,
+    sync::broadcast::{self, error::RecvError},
+    task::{Builder, JoinHandle},
+    time::{Duration, sleep},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, instrument, warn};
+
+const SLEEP: Duration = Duration::from_secs(10);
+
+/// Rolls up the readings of a sensor, apart from its logger
+pub(super) fn spawn<T: Clone + Readings + Send + 'static>(
+    receiver: broadcast::Receiver<T>,
+    cancellation: CancellationToken,
+) -> io::Result<JoinHandle<()>> {
+    let sensor = T::SENSOR.name();
+    Builder::new().name("rollup").spawn(Box::pin(async move {
+        loop {
+            // Returns once the files are finished, also when cancelled
+            let _ = run(receiver.resubscribe(), &cancellation).await;
+            if cancellation.is_cancelled() {
+                warn!("{sensor} rollup cancelled");
+                break;
+            }
+            warn!("loop {sensor} rollup");
+            select! {
+                biased;
+                _ = cancellation.cancelled() => {
+                    warn!("{sensor} rollup cancelled");
+                    break;
+                }
+                _ = sleep(SLEEP) => {},
+            };
+        }
+    }))
+}
+
+/// Aggregates the readings of a sensor per identifier over each rollup period,
+/// a folder per period (e.g. `temperature-1m`) with the logger flush and
+/// finish, the open periods are written and their files finished on every
+/// exit
+#[instrument(err, skip(receiver, cancellation))]
+async fn run<T: Clone + Readings>(
+    receiver: broadcast::Receiver<T>,
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    let settings = T::SENSOR.settings();
+    if settings.rollups.is_empty() {
+        cancellation.cancelled().await;
+        return Ok(());
+    }
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("Identifier", DataType::UInt64, false),
+        Field::new(
+            "Timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            false,
+        ),
+        Field::new("Min", DataType::Float64, false),
+        Field::new("Max", DataType::Float64, false),
+        Field::new("Mean", DataType::Float64, false),
+        Field::new("Count", DataType::UInt64, false),
+    ]));
+    let store = Arc::new(LocalFileSystem::new());
+    let mut rollups = settings
+        .rollups
+        .iter()
+        .map(|&period| Rollup::new(T::SENSOR.name(), period))
+        .collect::<Result<Vec<_>>>()?;
+    let result = roll(
+        receiver,
+        &schema,
+        &store,
+        settings,
+        &mut rollups,
+        cancellation,
+    )
+    .await;
+    for rollup in &mut rollups {
+        rollup.finish(&schema, store.clone(), settings).await?;
+    }
+    result
+}
+
+/// Pushes the readings of each message until cancelled
+async fn roll<T: Clone + Readings>(
+    mut receiver: broadcast::Receiver<T>,
+    schema: &SchemaRef,
+    store: &Arc<LocalFileSystem>,
+    settings: &Logger,
+    rollups: &mut [Rollup],
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    loop {
+        let message = select! {
+            biased;
+            _ = cancellation.cancelled() => return Ok(()),
+            message = receiver.recv() => message,
+        };
+        let message = match message {
+            Ok(message) => message,
+            Err(error @ RecvError::Lagged(_)) => {
+                warn!(%error);
+                metrics::lagged("logger rollup");
+                continue;
+            }
+            Err(error) => Err(error)?,
+        };
+        let readings = message.readings();
+        for rollup in rollups.iter_mut() {
+            if let Some((start, aggregates)) = rollup.push(message.date_time(), &readings) {
+                rollup
+                    .write(schema, store.clone(), settings, start, aggregates)
+                    .await?;
+            }
+        }
+    }
+}
+
+/// Readings of the current period with the writer of the closed ones
+struct Rollup {
+    /// Milliseconds
+    period: i64,
+    folder: String,
+    start: Option<i64>,
+    aggregates: BTreeMap<u64, Aggregate>,
+    writer: Option<Writer>,
+}
+
+impl Rollup {
+    fn new(sensor: &str, period: u64) -> Result<Self> {
+        if period == 0 {
+            bail!("zero {sensor} rollup period");
+        }
+        let suffix = match period {
+            period if period % 3600 == 0 => format!("{}h", period / 3600),
+            period if period % 60 == 0 => format!("{}m", period / 60),
+            period => format!("{period}s"),
+        };
+        Ok(Self {
+            period: period as i64 * 1000,
+            folder: format!("{sensor}-{suffix}"),
+            start: None,
+            aggregates: BTreeMap::new(),
+            writer: None,
+        })
+    }
+
+    /// Aggregates readings, returns the period they close with its aggregates,
+    /// the readings of an already closed period are dropped
+    fn push(
+        &mut self,
+        date_time: DateTime<Local>,
+        readings: &[(u64, f64)],
+    ) -> Option<(i64, BTreeMap<u64, Aggregate>)> {
+        let start = date_time.timestamp_millis().div_euclid(self.period) * self.period;
+        let mut closed = None;
+        match self.start {
+            Some(current) if start < current => return None,
+            Some(current) if current < start => {
+                closed = Some((current, take(&mut self.aggregates)));
+            }
+            _ => {}
+        }
+        self.start = Some(start);
+        for &(identifier, value) in readings {
+            self.aggregates.entry(identifier).or_default().push(value);
+        }
+        closed
+    }
+
+    /// Writes the aggregates of a period
+    async fn write(
+        &mut self,
+        schema: &SchemaRef,
+        store: Arc<dyn ObjectStore>,
+        settings: &Logger,
+        start: i64,
+        aggregates: BTreeMap<u64, Aggregate>,
+    ) -> Result<()> {
+        if aggregates.is_empty() {
+            return Ok(());
+        }
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => {
+                let date_time = Local
+                    .timestamp_millis_opt(start)
+                    .single()
+                    .ok_or_else(|| anyhow!("unexpected timestamp `{start}`"))?;
+                let writer = Writer::builder()
+                    .schema(schema.clone())
+                    .store(store)
+                    .folder(&self.folder)
+                    .date_time(date_time)
+                    .build()?;
+                self.writer.insert(writer)
+            }
+        };
+        let count = aggregates.len();
+        let (identifiers, aggregates): (Vec<_>, Vec<_>) = aggregates.into_iter().unzip();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(UInt64Array::from(identifiers)),
+                Arc::new(TimestampMillisecondArray::from_value(start, count)),
+                Arc::new(Float64Array::from_iter_values(
+                    aggregates.iter().map(|aggregate| aggregate.min),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    aggregates.iter().map(|aggregate| aggregate.max),
+                )),
+                Arc::new(Float64Array::from_iter_values(
+                    aggregates
+                        .iter()
+                        .map(|aggregate| aggregate.sum / aggregate.count as f64),
+                )),
+                Arc::new(UInt64Array::from_iter_values(
+                    aggregates.iter().map(|aggregate| aggregate.count),
+                )),
+            ],
+        )?;
+        writer.write(&batch).await?;
+        // Check for flush
+        if writer.in_progress_rows() >= settings.flush() {
+            info!("Flush {} {}", self.folder, writer.in_progress_rows());
+            writer.flush().await?;
+        }
+        // Check for writer
+        if writer.flushed_row_groups().len() >= settings.finish {
+            info!(
+                "Finish {} {}",
+                self.folder,
+                writer.flushed_row_groups().len()
+            );
+            writer.finish().await?;
+            self.writer.take();
+        }
+        Ok(())
+    }
+
+    /// Writes the open period, partial as its count shows, and finishes the
+    /// file
+    async fn finish(
+        &mut self,
+        schema: &SchemaRef,
+        store: Arc<dyn ObjectStore>,
+        settings: &Logger,
+    ) -> Result<()> {
+        if let Some(start) = self.start.take() {
+            let aggregates = take(&mut self.aggregates);
+            self.write(schema, store, settings, start, aggregates)
+                .await?;
+        }
+        let Some(mut writer) = self.writer.take() else {
+            return Ok(());
+        };
+        info!(
+            "Finish {} {}+{}",
+            self.folder,
+            writer.flushed_row_groups().len(),
+            writer.in_progress_rows(),
+        );
+        writer.finish().await?;
+        Ok(())
+    }
+}
+
+/// Running minimum, maximum, sum and count
+#[derive(Clone, Copy, Debug)]
+struct Aggregate {
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+}
+
+impl Aggregate {
+    fn push(&mut self, value: f64) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+impl Default for Aggregate {
+    fn default() -> Self {
+        Self {
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sum: 0.0,
+            count: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start of a minute
+    const MINUTE: i64 = 1_699_999_980_000;
+
+    fn at(milliseconds: i64) -> DateTime<Local> {
+        Local.timestamp_millis_opt(milliseconds).unwrap()
+    }
+
+    fn folder(period: u64) -> String {
+        Rollup::new("temperature", period).unwrap().folder
+    }
+
+    #[test]
+    fn aggregate() {
+        let mut aggregate = Aggregate::default();
+        for value in [2.0, -1.0, 5.0] {
+            aggregate.push(value);
+        }
+        assert_eq!(aggregate.min, -1.0);
+        assert_eq!(aggregate.max, 5.0);
+        assert_eq!(aggregate.sum, 6.0);
+        assert_eq!(aggregate.count, 3);
+    }
+
+    #[test]
+    fn folders() {
+        assert_eq!(folder(1), "temperature-1s");
+        assert_eq!(folder(90), "temperature-90s");
+        assert_eq!(folder(60), "temperature-1m");
+        assert_eq!(folder(300), "temperature-5m");
+        assert_eq!(folder(3600), "temperature-1h");
+        assert_eq!(folder(7200), "temperature-2h");
+        assert!(Rollup::new("temperature", 0).is_err());
+    }
+
+    #[test]
+    fn periods() {
+        let mut rollup = Rollup::new("temperature", 60).unwrap();
+        assert!(rollup.push(at(MINUTE), &[(1, 1.0), (2, 10.0)]).is_none());
+        assert!(rollup.push(at(MINUTE + 59_999), &[(1, 3.0)]).is_none());
+        let (start, aggregates) = rollup.push(at(MINUTE + 60_000), &[(1, 5.0)]).unwrap();
+        assert_eq!(start, MINUTE);
+        assert_eq!(aggregates.len(), 2);
+        assert_eq!(aggregates[&1].min, 1.0);
+        assert_eq!(aggregates[&1].max, 3.0);
+        assert_eq!(aggregates[&1].count, 2);
+        assert_eq!(aggregates[&2].count, 1);
+        // Skips an empty period
+        let (start, aggregates) = rollup.push(at(MINUTE + 180_000), &[]).unwrap();
+        assert_eq!(start, MINUTE + 60_000);
+        assert_eq!(aggregates[&1].sum, 5.0);
+        assert_eq!(rollup.start, Some(MINUTE + 180_000));
+    }
+
+    #[test]
+    fn closed() {
+        let mut rollup = Rollup::new("temperature", 60).unwrap();
+        rollup.push(at(MINUTE), &[(1, 1.0)]);
+        rollup.push(at(MINUTE + 60_000), &[(1, 2.0)]);
+        // Falls into the closed period
+        assert!(rollup.push(at(MINUTE + 30_000), &[(1, 100.0)]).is_none());
+        assert_eq!(rollup.start, Some(MINUTE + 60_000));
+        assert_eq!(rollup.aggregates[&1].max, 2.0);
+        assert_eq!(rollup.aggregates[&1].count, 1);
+        // Out of order within the current period
+        assert!(rollup.push(at(MINUTE + 90_000), &[(1, 4.0)]).is_none());
+        assert!(rollup.push(at(MINUTE + 70_000), &[(1, 3.0)]).is_none());
+        assert_eq!(rollup.aggregates[&1].count, 3);
+    }
+
+    #[test]
+    fn negative() {
+        let mut rollup = Rollup::new("temperature", 60).unwrap();
+        rollup.push(at(-1), &[(1, 1.0)]);
+        assert_eq!(rollup.start, Some(-60_000));
+        assert!(rollup.push(at(-60_000), &[(1, 2.0)]).is_none());
+        let (start, aggregates) = rollup.push(at(0), &[(1, 3.0)]).unwrap();
+        assert_eq!(start, -60_000);
+        assert_eq!(aggregates[&1].count, 2);
+    }
+}
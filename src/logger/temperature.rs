@@ -1,15 +1,19 @@
-use super::Writer;
-use crate::{SETTINGS, temperature::Message};
+use super::{TEMPERATURE as STATUS, Writer};
+use crate::{SETTINGS, experiment::EXPERIMENTS, metrics, sensor::Sensor, temperature::Message};
 use anyhow::Result;
 use arrow::{
-    array::{Float32Array, RecordBatch, TimestampMillisecondArray, UInt64Array},
+    array::{Float32Array, RecordBatch, StringArray, TimestampMillisecondArray, UInt64Array},
     datatypes::{DataType, Field, Schema, TimeUnit},
 };
 use object_store::local::LocalFileSystem;
+use scopeguard::defer;
 use std::sync::Arc;
 use tokio::{
     select,
-    sync::{broadcast, mpsc},
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc,
+    },
     task::{Builder, JoinHandle},
 };
 use tokio_util::sync::CancellationToken;
@@ -53,7 +57,15 @@ pub(crate) async fn read(
     sender: mpsc::Sender<Message>,
 ) -> Result<()> {
     loop {
-        let message = receiver.recv().await?;
+        let message = match receiver.recv().await {
+            Ok(message) => message,
+            Err(error @ RecvError::Lagged(_)) => {
+                warn!(%error);
+                metrics::lagged("logger temperature");
+                continue;
+            }
+            Err(error) => Err(error)?,
+        };
         sender.send(message).await?;
     }
 }
@@ -73,6 +85,9 @@ pub(crate) fn writer(
 
 #[instrument(err)]
 pub async fn write(mut receiver: mpsc::Receiver<Message>) -> Result<()> {
+    defer! {
+        STATUS.close();
+    }
     let schema = Arc::new(Schema::new(vec![
         Field::new("Identifier", DataType::UInt64, false),
         Field::new(
@@ -81,6 +96,7 @@ pub async fn write(mut receiver: mpsc::Receiver<Message>) -> Result<()> {
             false,
         ),
         Field::new("Temperature", DataType::Float32, false),
+        Field::new("Experiment", DataType::Utf8, true),
     ]));
     let store = Arc::new(LocalFileSystem::new());
     let builder = Writer::builder()
@@ -100,6 +116,7 @@ pub async fn write(mut receiver: mpsc::Receiver<Message>) -> Result<()> {
         };
         debug!(?writer);
         let count = identifiers.len();
+        let experiments = EXPERIMENTS.tags(&identifiers, date_time);
         let batch = RecordBatch::try_new(
             schema.clone(),
             vec![
@@ -109,19 +126,25 @@ pub async fn write(mut receiver: mpsc::Receiver<Message>) -> Result<()> {
                     count,
                 )),
                 Arc::new(Float32Array::from(values)),
+                Arc::new(StringArray::from(experiments)),
             ],
         )?;
         writer.write(&batch).await?;
+        metrics::rows(Sensor::Temperature, batch.num_rows());
         // Check for flush
         if writer.in_progress_rows() >= SETTINGS.temperature.flush() {
             info!("Flush {}", writer.in_progress_rows());
-            writer.flush().await?
+            writer.flush().await?;
+            metrics::flush(Sensor::Temperature);
         }
+        STATUS.update(writer);
         // Check for writer
         if writer.flushed_row_groups().len() >= SETTINGS.temperature.finish {
             info!("Finish {}", writer.flushed_row_groups().len());
             writer.finish().await?;
+            metrics::finish(Sensor::Temperature);
             maybe_writer.take();
+            STATUS.close();
         }
     }
     Ok(())
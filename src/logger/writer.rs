@@ -4,12 +4,16 @@ use arrow::datatypes::SchemaRef;
 use chrono::{DateTime, Local};
 use object_store::{ObjectStore, path::Path};
 use parquet::arrow::{AsyncArrowWriter, async_writer::ParquetObjectWriter};
+use serde::Serialize;
 use std::{
     fmt::{self, Debug, Formatter},
     fs::{File, create_dir_all},
     ops::{Deref, DerefMut},
     path::PathBuf,
-    sync::Arc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
 };
 use typed_builder::TypedBuilder;
 
@@ -88,3 +92,51 @@ impl DerefMut for Writer {
         &mut self.0
     }
 }
+
+/// Writer status, shared with the HTTP server
+#[derive(Debug)]
+pub(crate) struct Status {
+    open: AtomicBool,
+    in_progress_rows: AtomicUsize,
+    flushed_row_groups: AtomicUsize,
+}
+
+impl Status {
+    pub(crate) const fn new() -> Self {
+        Self {
+            open: AtomicBool::new(false),
+            in_progress_rows: AtomicUsize::new(0),
+            flushed_row_groups: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn update(&self, writer: &Writer) {
+        self.open.store(true, Ordering::Relaxed);
+        self.in_progress_rows
+            .store(writer.in_progress_rows(), Ordering::Relaxed);
+        self.flushed_row_groups
+            .store(writer.flushed_row_groups().len(), Ordering::Relaxed);
+    }
+
+    pub(crate) fn close(&self) {
+        self.open.store(false, Ordering::Relaxed);
+        self.in_progress_rows.store(0, Ordering::Relaxed);
+        self.flushed_row_groups.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn state(&self) -> State {
+        State {
+            open: self.open.load(Ordering::Relaxed),
+            in_progress_rows: self.in_progress_rows.load(Ordering::Relaxed),
+            flushed_row_groups: self.flushed_row_groups.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Writer state
+#[derive(Clone, Copy, Debug, Serialize)]
+pub(crate) struct State {
+    pub(crate) open: bool,
+    pub(crate) in_progress_rows: usize,
+    pub(crate) flushed_row_groups: usize,
+}
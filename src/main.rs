@@ -33,6 +33,7 @@ async fn main() -> Result<()> {
 
     // let args = Args::parse();
     info!("settings: {SETTINGS:?}");
+    info!("start: {}", *health::START);
 
     // let token = CancellationToken::new();
     // shutdown::serve(token.clone());
@@ -59,6 +60,36 @@ async fn run(cancellation: &CancellationToken) -> Result<()> {
         turbidity_receiver.resubscribe(),
         cancellation.clone(),
     )?;
+    http::spawn(
+        temperature_receiver.resubscribe(),
+        turbidity_receiver.resubscribe(),
+        cancellation.clone(),
+    )?;
+    flight::spawn(
+        temperature_receiver.resubscribe(),
+        turbidity_receiver.resubscribe(),
+        cancellation.clone(),
+    )?;
+    alarm::spawn(
+        temperature_receiver.resubscribe(),
+        turbidity_receiver.resubscribe(),
+        cancellation.clone(),
+    )?;
+    actuator::spawn(cancellation.clone())?;
+    annotation::spawn(cancellation.clone())?;
+    control::spawn(temperature_receiver.resubscribe(), cancellation.clone())?;
+    derived::spawn(
+        temperature_receiver.resubscribe(),
+        turbidity_receiver.resubscribe(),
+        cancellation.clone(),
+    )?;
+    growth::spawn(turbidity_receiver.resubscribe(), cancellation.clone())?;
+    turbidostat::spawn(turbidity_receiver.resubscribe(), cancellation.clone())?;
+    watchdog::spawn(
+        temperature_receiver.resubscribe(),
+        turbidity_receiver.resubscribe(),
+        cancellation.clone(),
+    )?;
     let temperature = temperature::spawn(temperature_sender)?;
     let turbidity = turbidity::spawn(turbidity_sender)?;
     select! {
@@ -68,10 +99,26 @@ async fn run(cancellation: &CancellationToken) -> Result<()> {
     Ok(())
 }
 
+mod actuator;
+mod alarm;
+mod annotation;
+mod archive;
+mod control;
+mod derived;
+mod experiment;
+mod flight;
+mod growth;
+mod health;
+mod http;
 mod log;
 mod logger;
+mod metrics;
 mod mqtt;
+mod output;
+mod sensor;
 mod settings;
 mod shutdown;
 mod temperature;
 mod turbidity;
+mod turbidostat;
+mod watchdog;
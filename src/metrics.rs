@@ -0,0 +1,136 @@
+use crate::sensor::Sensor;
+use anyhow::Result;
+use prometheus::{
+    GaugeVec, HistogramTimer, HistogramVec, IntCounter, IntCounterVec, TextEncoder,
+    register_gauge_vec, register_histogram_vec, register_int_counter, register_int_counter_vec,
+};
+use std::sync::LazyLock;
+
+pub(crate) const CONTENT_TYPE: &str = prometheus::TEXT_FORMAT;
+
+static TEMPERATURE: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "blcs_temperature",
+        "Latest temperature",
+        &["identifier", "name"]
+    )
+    .unwrap()
+});
+static TURBIDITY: LazyLock<GaugeVec> = LazyLock::new(|| {
+    register_gauge_vec!(
+        "blcs_turbidity",
+        "Latest turbidity",
+        &["identifier", "name"]
+    )
+    .unwrap()
+});
+static READ_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "blcs_modbus_read_duration_seconds",
+        "Modbus read latency",
+        &["sensor"]
+    )
+    .unwrap()
+});
+static READ_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "blcs_modbus_read_errors_total",
+        "Modbus read errors",
+        &["sensor"]
+    )
+    .unwrap()
+});
+static READ_TIMEOUTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "blcs_modbus_read_timeouts_total",
+        "Modbus read timeouts",
+        &["sensor"]
+    )
+    .unwrap()
+});
+static LAGGED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "blcs_broadcast_lagged_total",
+        "Broadcast lag events",
+        &["consumer"]
+    )
+    .unwrap()
+});
+static PUBLISH_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("blcs_mqtt_publish_failures_total", "MQTT publish failures").unwrap()
+});
+static ROWS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "blcs_logger_rows_written_total",
+        "Rows written by the logger",
+        &["sensor"]
+    )
+    .unwrap()
+});
+static FLUSHES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "blcs_logger_flushes_total",
+        "Row groups flushed by the logger",
+        &["sensor"]
+    )
+    .unwrap()
+});
+static FILES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "blcs_logger_files_finished_total",
+        "Files finished by the logger",
+        &["sensor"]
+    )
+    .unwrap()
+});
+
+/// Metrics in the text exposition format
+pub(crate) fn gather() -> Result<String> {
+    Ok(TextEncoder::new().encode_to_string(&prometheus::gather())?)
+}
+
+pub(crate) fn reading(sensor: Sensor, identifier: u64, value: f64) {
+    let gauge = match sensor {
+        Sensor::Temperature => &TEMPERATURE,
+        Sensor::Turbidity => &TURBIDITY,
+    };
+    let name = sensor.settings().name(identifier).unwrap_or_default();
+    gauge
+        .with_label_values(&[&format!("{identifier:x}"), name])
+        .set(value);
+}
+
+/// Observes the read duration on drop
+pub(crate) fn read_timer(sensor: Sensor) -> HistogramTimer {
+    READ_DURATION
+        .with_label_values(&[sensor.name()])
+        .start_timer()
+}
+
+pub(crate) fn read_error(sensor: Sensor) {
+    READ_ERRORS.with_label_values(&[sensor.name()]).inc();
+}
+
+pub(crate) fn read_timeout(sensor: Sensor) {
+    READ_TIMEOUTS.with_label_values(&[sensor.name()]).inc();
+}
+
+pub(crate) fn lagged(consumer: &str) {
+    LAGGED.with_label_values(&[consumer]).inc();
+}
+
+pub(crate) fn publish_failure() {
+    PUBLISH_FAILURES.inc();
+}
+
+pub(crate) fn rows(sensor: Sensor, rows: usize) {
+    ROWS.with_label_values(&[sensor.name()]).inc_by(rows as _);
+}
+
+pub(crate) fn flush(sensor: Sensor) {
+    FLUSHES.with_label_values(&[sensor.name()]).inc();
+}
+
+pub(crate) fn finish(sensor: Sensor) {
+    FILES.with_label_values(&[sensor.name()]).inc();
+}
@@ -0,0 +1,31 @@
+use super::{
+    MQTT_TOPIC_ALARM,
+    client::{Client, Properties},
+};
+use crate::alarm::{self, Event};
+use anyhow::Result;
+use rumqttc::QoS;
+use tokio::sync::broadcast;
+use tracing::instrument;
+
+const CONTENT_TYPE: &str = "application/json";
+
+/// Publishes alarm events
+#[instrument(err, skip(receiver))]
+pub(super) async fn run(mut receiver: broadcast::Receiver<Event>, client: Client) -> Result<()> {
+    loop {
+        let event = alarm::receive(&mut receiver, "mqtt alarm").await?;
+        client
+            .publish(
+                MQTT_TOPIC_ALARM,
+                QoS::AtLeastOnce,
+                false,
+                serde_json::to_vec(&event)?,
+                Properties {
+                    content_type: Some(CONTENT_TYPE.to_owned()),
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+}
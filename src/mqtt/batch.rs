@@ -0,0 +1,194 @@
+use crate::settings::Batch;
+use anyhow::{Result, anyhow};
+use arrow::{array::RecordBatch, compute::concat_batches, datatypes::SchemaRef};
+use std::future::pending;
+use tokio::{
+    sync::{mpsc, watch},
+    time::{Duration, Instant, sleep_until},
+};
+
+const CHANNEL_BUFFER: usize = 64;
+
+/// Reader to writer channel, every message when batching, the latest one
+/// otherwise
+pub(super) fn channel<T: Clone + Default>(settings: &Batch) -> (Sender<T>, Receiver<T>) {
+    if settings.rows > 1 || settings.delay > 0 {
+        let (sender, receiver) = mpsc::channel(CHANNEL_BUFFER);
+        (Sender::All(sender), Receiver::All(receiver))
+    } else {
+        let (sender, receiver) = watch::channel(T::default());
+        (Sender::Latest(sender), Receiver::Latest(receiver))
+    }
+}
+
+#[derive(Debug)]
+pub(super) enum Sender<T> {
+    Latest(watch::Sender<T>),
+    All(mpsc::Sender<T>),
+}
+
+impl<T: Send + Sync + 'static> Sender<T> {
+    pub(super) async fn send(&self, message: T) -> Result<()> {
+        match self {
+            Self::Latest(sender) => sender.send(message)?,
+            Self::All(sender) => sender.send(message).await?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub(super) enum Receiver<T> {
+    Latest(watch::Receiver<T>),
+    All(mpsc::Receiver<T>),
+}
+
+impl<T: Clone> Receiver<T> {
+    /// Cancel safe
+    pub(super) async fn recv(&mut self) -> Result<T> {
+        match self {
+            Self::Latest(receiver) => {
+                receiver.changed().await?;
+                Ok(receiver.borrow_and_update().clone())
+            }
+            Self::All(receiver) => receiver
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("closed readings channel")),
+        }
+    }
+}
+
+/// Collects readings until the maximum number of rows or the maximum delay is
+/// reached
+#[derive(Debug)]
+pub(super) struct Batcher {
+    schema: SchemaRef,
+    settings: &'static Batch,
+    batches: Vec<RecordBatch>,
+    rows: usize,
+    deadline: Option<Instant>,
+}
+
+impl Batcher {
+    pub(super) fn new(schema: SchemaRef, settings: &'static Batch) -> Self {
+        Self {
+            schema,
+            settings,
+            batches: Vec::new(),
+            rows: 0,
+            deadline: None,
+        }
+    }
+
+    pub(super) fn push(&mut self, batch: RecordBatch) {
+        if self.batches.is_empty() {
+            self.deadline = Some(Instant::now() + Duration::from_secs(self.settings.delay));
+        }
+        self.rows += batch.num_rows();
+        self.batches.push(batch);
+    }
+
+    /// Resolves when the deadline of the current batch is reached
+    pub(super) async fn expired(&self) {
+        match self.deadline {
+            Some(deadline) => sleep_until(deadline).await,
+            None => pending().await,
+        }
+    }
+
+    pub(super) fn ready(&self) -> bool {
+        !self.batches.is_empty()
+            && (self.rows >= self.settings.rows
+                || self
+                    .deadline
+                    .is_some_and(|deadline| deadline <= Instant::now()))
+    }
+
+    /// Takes collected readings and the latest reading
+    pub(super) fn take(&mut self) -> Result<(RecordBatch, RecordBatch)> {
+        let batch = concat_batches(&self.schema, &self.batches)?;
+        let latest = self.batches.pop().unwrap_or_else(|| batch.clone());
+        self.batches.clear();
+        self.rows = 0;
+        self.deadline = None;
+        Ok((batch, latest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::UInt64Array,
+        datatypes::{DataType, Field, Schema},
+    };
+    use std::sync::Arc;
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![Field::new(
+            "Identifier",
+            DataType::UInt64,
+            false,
+        )]))
+    }
+
+    fn batch(identifiers: &[u64]) -> RecordBatch {
+        RecordBatch::try_new(
+            schema(),
+            vec![Arc::new(UInt64Array::from(identifiers.to_vec()))],
+        )
+        .unwrap()
+    }
+
+    fn settings(rows: usize, delay: u64) -> &'static Batch {
+        Box::leak(Box::new(Batch { rows, delay }))
+    }
+
+    #[test]
+    fn rows() {
+        let mut batcher = Batcher::new(schema(), settings(3, 3600));
+        assert!(!batcher.ready());
+        batcher.push(batch(&[1, 2]));
+        assert!(!batcher.ready());
+        batcher.push(batch(&[3]));
+        assert!(batcher.ready());
+        let (batched, latest) = batcher.take().unwrap();
+        assert_eq!(batched.num_rows(), 3);
+        assert_eq!(latest.num_rows(), 1);
+        assert!(!batcher.ready());
+        batcher.push(batch(&[4]));
+        assert!(!batcher.ready());
+    }
+
+    #[test]
+    fn delay() {
+        let mut batcher = Batcher::new(schema(), settings(10, 0));
+        assert!(!batcher.ready());
+        batcher.push(batch(&[1]));
+        assert!(batcher.ready());
+        assert_eq!(batcher.take().unwrap().0.num_rows(), 1);
+        assert!(!batcher.ready());
+    }
+
+    #[tokio::test]
+    async fn channels() {
+        let (sender, mut receiver) = channel::<u64>(settings(0, 0));
+        assert!(matches!(sender, Sender::Latest(_)));
+        sender.send(1).await.unwrap();
+        sender.send(2).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), 2);
+
+        let (sender, mut receiver) = channel::<u64>(settings(2, 0));
+        assert!(matches!(sender, Sender::All(_)));
+        sender.send(1).await.unwrap();
+        sender.send(2).await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), 1);
+        assert_eq!(receiver.recv().await.unwrap(), 2);
+        drop(sender);
+        assert!(receiver.recv().await.is_err());
+
+        let (sender, _) = channel::<u64>(settings(0, 5));
+        assert!(matches!(sender, Sender::All(_)));
+    }
+}
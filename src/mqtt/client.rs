@@ -0,0 +1,249 @@
+use super::{CAPACITY, MQTT_HOST, MQTT_PORT};
+use crate::{
+    SETTINGS, metrics,
+    sensor::Sensor,
+    settings::{Topic, Version},
+};
+use anyhow::Result;
+use rumqttc::{
+    AsyncClient, Event, EventLoop as EventLoopV3, LastWill, MqttOptions, Packet, QoS,
+    v5::{
+        self,
+        mqttbytes::{
+            QoS as QoSV5,
+            v5::{LastWill as LastWillV5, Packet as PacketV5, PublishProperties},
+        },
+    },
+};
+use std::str;
+use tokio_util::bytes::Bytes;
+use tracing::trace;
+
+const SCHEMA_VERSION: &str = "1";
+
+/// Creates MQTT client and its event loop, MQTT version is taken from the
+/// settings
+pub(super) fn new(id: &str, last_will: LastWill) -> (Client, EventLoop) {
+    match SETTINGS.mqtt.version {
+        Version::V3 => {
+            let mut options = MqttOptions::new(id, MQTT_HOST, MQTT_PORT);
+            options.set_clean_session(true);
+            options.set_last_will(last_will);
+            let (client, event_loop) = AsyncClient::new(options, CAPACITY);
+            (Client::V3(client), EventLoop::V3(Box::new(event_loop)))
+        }
+        Version::V5 => {
+            let mut options = v5::MqttOptions::new(id, MQTT_HOST, MQTT_PORT);
+            options.set_clean_start(true);
+            options.set_last_will(last_will_v5(last_will));
+            let (client, event_loop) = v5::AsyncClient::new(options, CAPACITY);
+            (Client::V5(client), EventLoop::V5(Box::new(event_loop)))
+        }
+    }
+}
+
+/// MQTT client
+#[derive(Clone, Debug)]
+pub(crate) enum Client {
+    V3(AsyncClient),
+    V5(v5::AsyncClient),
+}
+
+impl Client {
+    /// Publishes payload, properties are ignored by MQTT v3.1.1
+    pub(crate) async fn publish(
+        &self,
+        topic: impl Into<String>,
+        qos: QoS,
+        retain: bool,
+        payload: impl Into<Bytes>,
+        properties: Properties,
+    ) -> Result<()> {
+        match self {
+            Self::V3(client) => client
+                .publish_bytes(topic, qos, retain, payload.into())
+                .await
+                .inspect_err(|_| metrics::publish_failure())?,
+            Self::V5(client) => client
+                .publish_bytes_with_properties(
+                    topic,
+                    self::qos(qos),
+                    retain,
+                    payload.into(),
+                    properties.into(),
+                )
+                .await
+                .inspect_err(|_| metrics::publish_failure())?,
+        }
+        Ok(())
+    }
+
+    /// Publishes payload without waiting, used from the event loop
+    pub(crate) fn try_publish(
+        &self,
+        topic: impl Into<String>,
+        qos: QoS,
+        retain: bool,
+        payload: impl Into<Bytes>,
+        properties: Properties,
+    ) -> Result<()> {
+        match self {
+            Self::V3(client) => client
+                .try_publish(topic, qos, retain, payload.into())
+                .inspect_err(|_| metrics::publish_failure())?,
+            Self::V5(client) => client
+                .try_publish_with_properties(
+                    topic,
+                    self::qos(qos),
+                    retain,
+                    payload.into(),
+                    properties.into(),
+                )
+                .inspect_err(|_| metrics::publish_failure())?,
+        }
+        Ok(())
+    }
+
+    /// Subscribes without waiting, used from the event loop
+    pub(crate) fn try_subscribe(&self, topic: impl Into<String>, qos: QoS) -> Result<()> {
+        match self {
+            Self::V3(client) => client.try_subscribe(topic, qos)?,
+            Self::V5(client) => client.try_subscribe(topic, self::qos(qos))?,
+        }
+        Ok(())
+    }
+}
+
+/// MQTT event loop
+pub(crate) enum EventLoop {
+    V3(Box<EventLoopV3>),
+    V5(Box<v5::EventLoop>),
+}
+
+impl EventLoop {
+    /// Replaces the last will, registered on the next connection
+    pub(crate) fn set_last_will(&mut self, last_will: LastWill) {
+        match self {
+            Self::V3(event_loop) => {
+                event_loop.mqtt_options.set_last_will(last_will);
+            }
+            Self::V5(event_loop) => {
+                event_loop.options.set_last_will(last_will_v5(last_will));
+            }
+        }
+    }
+
+    /// Polls the next notification, uninteresting events are only traced
+    pub(crate) async fn poll(&mut self) -> Result<Option<Notification>> {
+        Ok(match self {
+            Self::V3(event_loop) => match event_loop.poll().await? {
+                Event::Incoming(Packet::ConnAck(_)) => Some(Notification::Connected),
+                Event::Incoming(Packet::Publish(publish)) => Some(Notification::Publish(Publish {
+                    topic: publish.topic,
+                    payload: publish.payload,
+                    response_topic: None,
+                    correlation_data: None,
+                })),
+                event => {
+                    trace!(?event);
+                    None
+                }
+            },
+            Self::V5(event_loop) => match event_loop.poll().await? {
+                v5::Event::Incoming(PacketV5::ConnAck(_)) => Some(Notification::Connected),
+                v5::Event::Incoming(PacketV5::Publish(publish)) => {
+                    let properties = publish.properties.unwrap_or_default();
+                    Some(Notification::Publish(Publish {
+                        topic: str::from_utf8(&publish.topic)?.to_owned(),
+                        payload: publish.payload,
+                        response_topic: properties.response_topic,
+                        correlation_data: properties.correlation_data,
+                    }))
+                }
+                event => {
+                    trace!(?event);
+                    None
+                }
+            },
+        })
+    }
+}
+
+/// Event loop notification
+#[derive(Clone, Debug)]
+pub(crate) enum Notification {
+    Connected,
+    Publish(Publish),
+}
+
+/// Incoming publish
+#[derive(Clone, Debug)]
+pub(crate) struct Publish {
+    pub(crate) topic: String,
+    pub(crate) payload: Bytes,
+    pub(crate) response_topic: Option<String>,
+    pub(crate) correlation_data: Option<Bytes>,
+}
+
+/// Publish properties (MQTT v5)
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Properties {
+    pub(crate) content_type: Option<String>,
+    pub(crate) message_expiry: Option<u32>,
+    pub(crate) user_properties: Vec<(String, String)>,
+    pub(crate) response_topic: Option<String>,
+    pub(crate) correlation_data: Option<Bytes>,
+}
+
+impl Properties {
+    /// Properties of sensor readings
+    pub(crate) fn reading(sensor: Sensor, topic: &Topic) -> Self {
+        Self {
+            content_type: Some(topic.encoding.content_type().to_owned()),
+            message_expiry: topic.expiry,
+            user_properties: vec![
+                ("sensor".to_owned(), sensor.to_string()),
+                ("schema".to_owned(), SCHEMA_VERSION.to_owned()),
+            ]
+            .into_iter()
+            .chain(
+                sensor
+                    .unit()
+                    .map(|unit| ("unit".to_owned(), unit.to_owned())),
+            )
+            .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Properties> for PublishProperties {
+    fn from(value: Properties) -> Self {
+        Self {
+            content_type: value.content_type,
+            message_expiry_interval: value.message_expiry,
+            user_properties: value.user_properties,
+            response_topic: value.response_topic,
+            correlation_data: value.correlation_data,
+            ..Default::default()
+        }
+    }
+}
+
+fn last_will_v5(last_will: LastWill) -> LastWillV5 {
+    LastWillV5::new(
+        last_will.topic,
+        last_will.message,
+        qos(last_will.qos),
+        last_will.retain,
+        None,
+    )
+}
+
+fn qos(qos: QoS) -> QoSV5 {
+    match qos {
+        QoS::AtMostOnce => QoSV5::AtMostOnce,
+        QoS::AtLeastOnce => QoSV5::AtLeastOnce,
+        QoS::ExactlyOnce => QoSV5::ExactlyOnce,
+    }
+}
@@ -0,0 +1,140 @@
+use super::{
+    MQTT_TOPIC_COMMANDER,
+    client::{Client, Properties, Publish},
+    status::VERSION,
+};
+use crate::{
+    actuator::{ACTUATORS, Source},
+    alarm::ALARMS,
+    annotation::{self, ANNOTATIONS, Annotate},
+    control::CONTROLLERS,
+    experiment::{EXPERIMENTS, Start},
+    health::{self, START},
+    turbidostat::TURBIDOSTATS,
+};
+use anyhow::Result;
+use rumqttc::QoS;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::{sync::mpsc, task::spawn_blocking};
+use tracing::{info, instrument, warn};
+
+const CONTENT_TYPE: &str = "application/json";
+
+/// Executes commands and publishes responses to the response topic (MQTT v5)
+/// or to the default response topic (MQTT v3.1.1)
+#[instrument(err)]
+pub(super) async fn run(mut receiver: mpsc::Receiver<Publish>, client: Client) -> Result<()> {
+    while let Some(publish) = receiver.recv().await {
+        let response = match serde_json::from_slice(&publish.payload) {
+            Ok(command) => {
+                info!(?command);
+                match execute(command).await {
+                    Ok(value) => Response::Ok(value),
+                    Err(error) => Response::Error(error.to_string()),
+                }
+            }
+            Err(error) => Response::Error(error.to_string()),
+        };
+        if let Response::Error(error) = &response {
+            warn!(%error);
+        }
+        let topic = publish
+            .response_topic
+            .unwrap_or_else(|| format!("{MQTT_TOPIC_COMMANDER}/response"));
+        client
+            .publish(
+                topic,
+                QoS::AtLeastOnce,
+                false,
+                serde_json::to_vec(&response)?,
+                Properties {
+                    content_type: Some(CONTENT_TYPE.to_owned()),
+                    correlation_data: publish.correlation_data,
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+async fn execute(command: Command) -> Result<Value> {
+    Ok(match command {
+        Command::Ping => json!("pong"),
+        Command::Status => json!({
+            "version": VERSION,
+            "start": *START,
+            "temperature": health::TEMPERATURE.state(),
+            "turbidity": health::TURBIDITY.state(),
+        }),
+        Command::Alarms => json!(ALARMS.active()),
+        Command::Acknowledge { rule, identifier } => {
+            json!(ALARMS.acknowledge(&rule, u64::from_str_radix(&identifier, 16)?)?)
+        }
+        Command::Control => json!(CONTROLLERS.states()),
+        Command::Setpoint {
+            controller,
+            setpoint,
+        } => json!(CONTROLLERS.set(&controller, setpoint)?),
+        Command::Actuators => json!(ACTUATORS.states()),
+        Command::Turbidostats => json!(TURBIDOSTATS.states()),
+        Command::Experiments => json!(EXPERIMENTS.list()),
+        Command::StartExperiment(start) => json!(EXPERIMENTS.start(start)?),
+        Command::StopExperiment { name } => json!(EXPERIMENTS.stop(&name)?),
+        Command::Annotate(annotate) => json!(ANNOTATIONS.annotate(annotate)?),
+        Command::Annotations(query) => {
+            json!(spawn_blocking(move || ANNOTATIONS.query(&query)).await??)
+        }
+        Command::Actuate { actuator, value } => {
+            json!(ACTUATORS.set(&actuator, value, Source::Command)?)
+        }
+    })
+}
+
+/// Command
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub(crate) enum Command {
+    Ping,
+    Status,
+    /// Raised and acknowledged alarms
+    Alarms,
+    Acknowledge {
+        rule: String,
+        /// Hexadecimal sensor identifier
+        identifier: String,
+    },
+    /// Controller states
+    Control,
+    Setpoint {
+        controller: String,
+        setpoint: f64,
+    },
+    /// Actuator states
+    Actuators,
+    /// Holds until the next scheduled switch
+    Actuate {
+        actuator: String,
+        value: u16,
+    },
+    /// Turbidostat states
+    Turbidostats,
+    /// Running and stopped experiments
+    Experiments,
+    StartExperiment(Start),
+    StopExperiment {
+        name: String,
+    },
+    Annotate(Annotate),
+    /// Annotations within `from..to`
+    Annotations(annotation::Query),
+}
+
+/// Command response
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    Ok(Value),
+    Error(String),
+}
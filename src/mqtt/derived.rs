@@ -0,0 +1,39 @@
+use super::{
+    MQTT_TOPIC_DERIVED,
+    client::{Client, Properties},
+};
+use crate::{derived::Message, metrics};
+use anyhow::Result;
+use rumqttc::QoS;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{instrument, warn};
+
+const CONTENT_TYPE: &str = "application/json";
+
+/// Publishes derived readings
+#[instrument(err, skip(receiver))]
+pub(super) async fn run(mut receiver: broadcast::Receiver<Message>, client: Client) -> Result<()> {
+    loop {
+        let message = match receiver.recv().await {
+            Ok(message) => message,
+            Err(error @ RecvError::Lagged(_)) => {
+                warn!(%error);
+                metrics::lagged("mqtt derived");
+                continue;
+            }
+            Err(error) => Err(error)?,
+        };
+        client
+            .publish(
+                MQTT_TOPIC_DERIVED,
+                QoS::AtLeastOnce,
+                false,
+                serde_json::to_vec(&message)?,
+                Properties {
+                    content_type: Some(CONTENT_TYPE.to_owned()),
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+}
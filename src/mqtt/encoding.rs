@@ -0,0 +1,98 @@
+use anyhow::Result;
+use arrow::{
+    array::RecordBatch,
+    ipc::{
+        CompressionType,
+        writer::{IpcWriteOptions, StreamWriter},
+    },
+    json::ArrayWriter,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::Display;
+use tokio_util::bytes::{BufMut as _, Bytes, BytesMut};
+
+/// Payload encoding
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Encoding {
+    #[default]
+    Arrow,
+    Json,
+    Cbor,
+    #[serde(alias = "msgpack")]
+    MessagePack,
+}
+
+impl Encoding {
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            Self::Arrow => "application/vnd.apache.arrow.stream",
+            Self::Json => "application/json",
+            Self::Cbor => "application/cbor",
+            Self::MessagePack => "application/msgpack",
+        }
+    }
+
+    /// Topic with the encoding suffix, Arrow IPC topics are left as is
+    pub(crate) fn topic(self, topic: impl Display) -> String {
+        match self {
+            Self::Arrow => topic.to_string(),
+            Self::Json => format!("{topic}/json"),
+            Self::Cbor => format!("{topic}/cbor"),
+            Self::MessagePack => format!("{topic}/msgpack"),
+        }
+    }
+
+    /// Encodes record batch, compression is applied to Arrow IPC only
+    pub(crate) fn encode(
+        self,
+        batch: &RecordBatch,
+        compression: Option<Compression>,
+    ) -> Result<Bytes> {
+        let mut bytes = BytesMut::new().writer();
+        match self {
+            Self::Arrow => {
+                let options =
+                    IpcWriteOptions::default().try_with_compression(compression.map(Into::into))?;
+                let mut writer =
+                    StreamWriter::try_new_with_options(&mut bytes, &batch.schema(), options)?;
+                writer.write(batch)?;
+                writer.finish()?;
+            }
+            Self::Json => {
+                let mut writer = ArrayWriter::new(&mut bytes);
+                writer.write(batch)?;
+                writer.finish()?;
+            }
+            Self::Cbor => ciborium::into_writer(&rows(batch)?, &mut bytes)?,
+            Self::MessagePack => rmp_serde::encode::write_named(&mut bytes, &rows(batch)?)?,
+        }
+        Ok(bytes.into_inner().freeze())
+    }
+}
+
+/// Arrow IPC body compression
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Compression {
+    Lz4,
+    Zstd,
+}
+
+impl From<Compression> for CompressionType {
+    fn from(value: Compression) -> Self {
+        match value {
+            Compression::Lz4 => CompressionType::LZ4_FRAME,
+            Compression::Zstd => CompressionType::ZSTD,
+        }
+    }
+}
+
+/// Record batch as JSON rows
+fn rows(batch: &RecordBatch) -> Result<Value> {
+    let mut writer = ArrayWriter::new(Vec::new());
+    writer.write(batch)?;
+    writer.finish()?;
+    Ok(serde_json::from_slice(&writer.into_inner())?)
+}
@@ -0,0 +1,39 @@
+use super::{
+    MQTT_TOPIC_GROWTH,
+    client::{Client, Properties},
+};
+use crate::{growth::Estimate, metrics};
+use anyhow::Result;
+use rumqttc::QoS;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{instrument, warn};
+
+const CONTENT_TYPE: &str = "application/json";
+
+/// Publishes growth rate estimates
+#[instrument(err, skip(receiver))]
+pub(super) async fn run(mut receiver: broadcast::Receiver<Estimate>, client: Client) -> Result<()> {
+    loop {
+        let estimate = match receiver.recv().await {
+            Ok(estimate) => estimate,
+            Err(error @ RecvError::Lagged(_)) => {
+                warn!(%error);
+                metrics::lagged("mqtt growth");
+                continue;
+            }
+            Err(error) => Err(error)?,
+        };
+        client
+            .publish(
+                MQTT_TOPIC_GROWTH,
+                QoS::AtLeastOnce,
+                false,
+                serde_json::to_vec(&estimate)?,
+                Properties {
+                    content_type: Some(CONTENT_TYPE.to_owned()),
+                    ..Default::default()
+                },
+            )
+            .await?;
+    }
+}
@@ -0,0 +1,162 @@
+use super::{
+    MQTT_TOPIC_HOMEASSISTANT, MQTT_TOPIC_STATUS,
+    client::{Client, Properties},
+    status::VERSION,
+};
+use crate::{
+    SETTINGS, metrics, sensor::Sensor, temperature::Message as TemperatureMessage,
+    turbidity::Message as TurbidityMessage,
+};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use rumqttc::QoS;
+use serde::Serialize;
+use serde_json::json;
+use std::{collections::HashSet, future::pending};
+use tokio::{
+    select,
+    sync::broadcast::{self, error::RecvError},
+};
+use tracing::{instrument, warn};
+
+const CONTENT_TYPE: &str = "application/json";
+
+/// Publishes Home Assistant discovery config for every new sensor and its JSON
+/// state
+#[instrument(err)]
+pub(super) async fn run(
+    mut temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    mut turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    client: Client,
+) -> Result<()> {
+    let Some(settings) = &SETTINGS.mqtt.homeassistant else {
+        return pending().await;
+    };
+    let mut discovery = Discovery {
+        prefix: &settings.prefix,
+        client,
+        known: HashSet::new(),
+    };
+    loop {
+        select! {
+            result = temperature_receiver.recv() => match result {
+                Ok(message) => {
+                    let TemperatureMessage { identifiers, date_time, values } = message;
+                    for (identifier, value) in identifiers.into_iter().zip(values) {
+                        discovery
+                            .publish(Sensor::Temperature, identifier, value, date_time)
+                            .await?;
+                    }
+                }
+                Err(error @ RecvError::Lagged(_)) => {
+                    warn!(%error);
+                    metrics::lagged("homeassistant");
+                }
+                Err(error) => Err(error)?,
+            },
+            result = turbidity_receiver.recv() => match result {
+                Ok(message) => {
+                    let TurbidityMessage { identifier, date_time, value } = message;
+                    discovery
+                        .publish(Sensor::Turbidity, identifier, value, date_time)
+                        .await?;
+                }
+                Err(error @ RecvError::Lagged(_)) => {
+                    warn!(%error);
+                    metrics::lagged("homeassistant");
+                }
+                Err(error) => Err(error)?,
+            },
+        }
+    }
+}
+
+/// Discovery
+struct Discovery<'a> {
+    prefix: &'a str,
+    client: Client,
+    known: HashSet<(Sensor, u64)>,
+}
+
+impl Discovery<'_> {
+    /// Publishes state, preceded by discovery config for an unknown sensor
+    async fn publish(
+        &mut self,
+        sensor: Sensor,
+        identifier: u64,
+        value: impl Serialize,
+        date_time: DateTime<Local>,
+    ) -> Result<()> {
+        let state_topic = format!(
+            "{MQTT_TOPIC_HOMEASSISTANT}/{}/{identifier:x}",
+            sensor.name()
+        );
+        if self.known.insert((sensor, identifier)) {
+            let unique_id = format!("blcs_{}_{identifier:x}", sensor.name());
+            let name = sensor.settings().name(identifier).map_or_else(
+                || format!("{} {identifier:x}", sensor.name()),
+                ToOwned::to_owned,
+            );
+            let mut config = json!({
+                "name": name,
+                "unique_id": unique_id,
+                "object_id": unique_id,
+                "state_topic": state_topic,
+                "value_template": "{{ value_json.value }}",
+                "state_class": "measurement",
+                "availability_topic": MQTT_TOPIC_STATUS,
+                "availability_template": "{{ value_json.state }}",
+                "payload_available": "online",
+                "payload_not_available": "offline",
+                "device": {
+                    "identifiers": ["ippras_blcs_server"],
+                    "name": "BLCS server",
+                    "manufacturer": "IPPRAS",
+                    "sw_version": VERSION,
+                },
+            });
+            if let Some(unit) = sensor.unit() {
+                config["unit_of_measurement"] = unit.into();
+            }
+            if let Some(device_class) = device_class(sensor) {
+                config["device_class"] = device_class.into();
+            }
+            self.client
+                .publish(
+                    format!("{}/sensor/{unique_id}/config", self.prefix),
+                    QoS::AtLeastOnce,
+                    true,
+                    serde_json::to_vec(&config)?,
+                    properties(),
+                )
+                .await?;
+        }
+        let state = json!({
+            "value": value,
+            "timestamp": date_time,
+        });
+        self.client
+            .publish(
+                state_topic,
+                QoS::AtMostOnce,
+                false,
+                serde_json::to_vec(&state)?,
+                properties(),
+            )
+            .await
+    }
+}
+
+fn properties() -> Properties {
+    Properties {
+        content_type: Some(CONTENT_TYPE.to_owned()),
+        ..Default::default()
+    }
+}
+
+fn device_class(sensor: Sensor) -> Option<&'static str> {
+    match sensor {
+        Sensor::Temperature => Some("temperature"),
+        Sensor::Turbidity => None,
+    }
+}
@@ -1,11 +1,15 @@
-use crate::{temperature::Message as TemperatureMessage, turbidity::Message as TurbidityMessage};
+use self::client::{Client, EventLoop, Notification};
+use crate::{
+    alarm::ALARMS, derived::DERIVED, growth::ESTIMATES, temperature::Message as TemperatureMessage,
+    turbidity::Message as TurbidityMessage,
+};
 use anyhow::Result;
-use rumqttc::{AsyncClient, EventLoop, MqttOptions};
+use rumqttc::QoS;
 use scopeguard::defer;
 use std::io;
 use tokio::{
     select,
-    sync::broadcast,
+    sync::{broadcast, mpsc},
     task::{Builder, JoinHandle},
 };
 use tokio_util::sync::CancellationToken;
@@ -16,7 +20,15 @@ const MQTT_PORT: u16 = 1883;
 const MQTT_ID: &str = "ippras.ru/blcs/server";
 const MQTT_TOPIC_DTEC: &str = "ippras.ru/blcs/dtec";
 const MQTT_TOPIC_ATUC: &str = "ippras.ru/blcs/atuc";
+const MQTT_TOPIC_STATUS: &str = "ippras.ru/blcs/status";
+const MQTT_TOPIC_COMMANDER: &str = "ippras.ru/blcs/commander";
+const MQTT_TOPIC_HOMEASSISTANT: &str = "ippras.ru/blcs/homeassistant";
+const MQTT_TOPIC_QUERY: &str = "ippras.ru/blcs/query";
+const MQTT_TOPIC_ALARM: &str = "ippras.ru/blcs/alarm";
+const MQTT_TOPIC_GROWTH: &str = "ippras.ru/blcs/growth";
+const MQTT_TOPIC_DERIVED: &str = "ippras.ru/blcs/derived";
 const CAPACITY: usize = 9;
+const CHANNEL_BUFFER: usize = 9;
 
 pub(crate) fn spawn(
     temperature_receiver: broadcast::Receiver<TemperatureMessage>,
@@ -44,13 +56,16 @@ async fn run(
     turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
     cancellation: CancellationToken,
 ) -> Result<()> {
-    let mut options = MqttOptions::new(MQTT_ID, MQTT_HOST, MQTT_PORT);
-    options.set_clean_session(true);
-    let (client, event_loop) = AsyncClient::new(options, CAPACITY);
+    let (client, event_loop) = client::new(MQTT_ID, status::last_will()?);
+    let commander_channel = mpsc::channel(CHANNEL_BUFFER);
+    let query_channel = mpsc::channel(CHANNEL_BUFFER);
     // Event loop
-    let event_loop = Builder::new()
-        .name("event loop")
-        .spawn(Box::pin(read(event_loop)))?;
+    let event_loop = Builder::new().name("event loop").spawn(Box::pin(read(
+        event_loop,
+        client.clone(),
+        commander_channel.0,
+        query_channel.0,
+    )))?;
     let abort_handle = event_loop.abort_handle();
     defer! {
         abort_handle.abort();
@@ -66,23 +81,88 @@ async fn run(
         client.clone(),
         cancellation.clone(),
     );
+    let status = status::run(client.clone());
+    let alarm = alarm::run(ALARMS.subscribe(), client.clone());
+    let growth = growth::run(ESTIMATES.subscribe(), client.clone());
+    let derived = derived::run(DERIVED.subscribe(), client.clone());
+    let commander = commander::run(commander_channel.1, client.clone());
+    let query = query::run(query_channel.1, client.clone());
+    let homeassistant = homeassistant::run(
+        temperature_receiver.resubscribe(),
+        turbidity_receiver.resubscribe(),
+        client.clone(),
+    );
+    let sparkplug = sparkplug::run(
+        temperature_receiver.resubscribe(),
+        turbidity_receiver.resubscribe(),
+    );
     select! {
         result = temperature => result?,
         result = turbidity => result?,
+        result = status => result?,
+        result = alarm => result?,
+        result = growth => result?,
+        result = derived => result?,
+        result = commander => result?,
+        result = query => result?,
+        result = homeassistant => result?,
+        result = sparkplug => result?,
         result = event_loop => result?,
     }
     Ok(())
 }
 
 #[instrument(skip(event_loop))]
-async fn read(mut event_loop: EventLoop) {
+async fn read(
+    mut event_loop: EventLoop,
+    client: Client,
+    commander: mpsc::Sender<client::Publish>,
+    query: mpsc::Sender<client::Publish>,
+) {
     loop {
         match event_loop.poll().await {
-            Ok(event) => trace!(?event),
+            Ok(Some(Notification::Connected)) => {
+                trace!("connected");
+                if let Err(error) = status::online(&client) {
+                    error!(%error);
+                }
+                for topic in [MQTT_TOPIC_COMMANDER, MQTT_TOPIC_QUERY] {
+                    if let Err(error) = client.try_subscribe(topic, QoS::AtLeastOnce) {
+                        error!(%error);
+                    }
+                }
+            }
+            Ok(Some(Notification::Publish(publish))) => match &*publish.topic {
+                MQTT_TOPIC_COMMANDER => {
+                    if let Err(error) = commander.try_send(publish) {
+                        warn!(%error);
+                    }
+                }
+                MQTT_TOPIC_QUERY => {
+                    if let Err(error) = query.try_send(publish) {
+                        warn!(%error);
+                    }
+                }
+                topic => warn!(r#"unexpected MQTT topic: "{topic}""#),
+            },
+            Ok(None) => {}
             Err(error) => error!(?error),
         }
     }
 }
 
+pub(crate) use self::encoding::{Compression, Encoding};
+
+mod alarm;
+mod batch;
+mod client;
+mod commander;
+mod derived;
+mod encoding;
+mod growth;
+mod homeassistant;
+mod query;
+mod sparkplug;
+mod status;
 mod temperature;
 mod turbidity;
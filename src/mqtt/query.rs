@@ -0,0 +1,136 @@
+use super::{
+    MQTT_TOPIC_QUERY,
+    client::{Client, Properties, Publish},
+};
+use crate::{
+    annotation::{ANNOTATIONS, Annotation},
+    archive::{self, Query},
+};
+use anyhow::Result;
+use polars::prelude::*;
+use rumqttc::QoS;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::{sync::mpsc, task::spawn_blocking};
+use tracing::{info, instrument, warn};
+
+const CHUNK: usize = 10_000;
+const CONTENT_TYPE_ARROW: &str = "application/vnd.apache.arrow.stream";
+const CONTENT_TYPE_JSON: &str = "application/json";
+
+/// Answers archive queries with Arrow IPC streams of at most `CHUNK` rows each,
+/// then a JSON message of the annotations if requested, followed by an empty
+/// message marking the end of the response
+#[instrument(err)]
+pub(super) async fn run(mut receiver: mpsc::Receiver<Publish>, client: Client) -> Result<()> {
+    while let Some(publish) = receiver.recv().await {
+        let request = serde_json::from_slice::<Request>(&publish.payload);
+        let topic = publish
+            .response_topic
+            .or_else(|| request.as_ref().ok()?.reply.clone())
+            .unwrap_or_else(|| format!("{MQTT_TOPIC_QUERY}/response"));
+        let properties = |content_type: &str, user_properties| Properties {
+            content_type: Some(content_type.to_owned()),
+            correlation_data: publish.correlation_data.clone(),
+            user_properties,
+            ..Default::default()
+        };
+        match query(request).await {
+            Ok((chunks, annotations)) => {
+                let count = chunks.len();
+                for (index, chunk) in chunks.into_iter().enumerate() {
+                    let user_properties = vec![
+                        ("chunk".to_owned(), index.to_string()),
+                        ("chunks".to_owned(), count.to_string()),
+                    ];
+                    client
+                        .publish(
+                            &topic,
+                            QoS::AtLeastOnce,
+                            false,
+                            chunk,
+                            properties(CONTENT_TYPE_ARROW, user_properties),
+                        )
+                        .await?;
+                }
+                if let Some(annotations) = annotations {
+                    client
+                        .publish(
+                            &topic,
+                            QoS::AtLeastOnce,
+                            false,
+                            serde_json::to_vec(&json!({ "annotations": annotations }))?,
+                            properties(CONTENT_TYPE_JSON, Vec::new()),
+                        )
+                        .await?;
+                }
+                client
+                    .publish(
+                        topic,
+                        QoS::AtLeastOnce,
+                        false,
+                        Vec::new(),
+                        properties(CONTENT_TYPE_ARROW, Vec::new()),
+                    )
+                    .await?;
+            }
+            Err(error) => {
+                warn!(%error);
+                client
+                    .publish(
+                        topic,
+                        QoS::AtLeastOnce,
+                        false,
+                        serde_json::to_vec(&json!({ "error": error.to_string() }))?,
+                        properties(CONTENT_TYPE_JSON, Vec::new()),
+                    )
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn query(
+    request: serde_json::Result<Request>,
+) -> Result<(Vec<Vec<u8>>, Option<Vec<Annotation>>)> {
+    let query = request?.query;
+    info!(?query);
+    spawn_blocking(move || {
+        let chunks = chunks(archive::scan(&query)?)?;
+        let annotations = query
+            .annotations
+            .then(|| ANNOTATIONS.concerning(&query))
+            .transpose()?;
+        Ok((chunks, annotations))
+    })
+    .await?
+}
+
+/// Splits data frame into Arrow IPC streams
+fn chunks(data_frame: DataFrame) -> Result<Vec<Vec<u8>>> {
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    loop {
+        let mut chunk = data_frame.slice(offset as _, CHUNK);
+        let mut bytes = Vec::new();
+        IpcStreamWriter::new(&mut bytes)
+            .with_compat_level(CompatLevel::oldest())
+            .finish(&mut chunk)?;
+        chunks.push(bytes);
+        offset += CHUNK;
+        if offset >= data_frame.height() {
+            break;
+        }
+    }
+    Ok(chunks)
+}
+
+/// Archive query request
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(flatten)]
+    query: Query,
+    /// Reply topic, MQTT v5 response topic takes precedence
+    reply: Option<String>,
+}
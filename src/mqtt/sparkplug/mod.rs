@@ -0,0 +1,333 @@
+use self::payload::{Metric, Payload, Value};
+use super::{
+    MQTT_ID,
+    client::{self, Client, EventLoop, Notification, Properties},
+};
+use crate::{
+    SETTINGS, metrics, settings::Sparkplug, temperature::Message as TemperatureMessage,
+    turbidity::Message as TurbidityMessage,
+};
+use anyhow::Result;
+use chrono::Local;
+use prost::Message as _;
+use rumqttc::{LastWill, QoS};
+use scopeguard::defer;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future::pending,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::{
+    select,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc,
+    },
+    task::Builder,
+};
+use tracing::{error, info, instrument, trace, warn};
+
+const NAMESPACE: &str = "spBv1.0";
+const BD_SEQ: &str = "bdSeq";
+const REBIRTH: &str = "Node Control/Rebirth";
+const TEMPERATURE: &str = "temperature";
+const TURBIDITY: &str = "turbidity";
+const CHANNEL_BUFFER: usize = 9;
+
+/// Birth/death sequence number, incremented on every new session
+static SESSION: AtomicU64 = AtomicU64::new(0);
+
+/// Publishes readings as Sparkplug B edge node with temperature and turbidity
+/// devices, uses its own connection as NDEATH is the last will
+#[instrument(err)]
+pub(super) async fn run(
+    mut temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    mut turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+) -> Result<()> {
+    let Some(settings) = &SETTINGS.mqtt.sparkplug else {
+        return pending().await;
+    };
+    let topic = Topic { settings };
+    let bd_seq = next_bd_seq();
+    let (client, event_loop) = client::new(&format!("{MQTT_ID}/sparkplug"), topic.death(bd_seq));
+    let (sender, mut receiver) = mpsc::channel(CHANNEL_BUFFER);
+    let event_loop = Builder::new()
+        .name("sparkplug event loop")
+        .spawn(Box::pin(read(
+            event_loop,
+            client.clone(),
+            topic,
+            bd_seq,
+            sender,
+        )))?;
+    let abort_handle = event_loop.abort_handle();
+    defer! {
+        abort_handle.abort();
+    }
+    let mut node = Node {
+        client,
+        topic,
+        bd_seq,
+        seq: 0,
+        born: false,
+        devices: BTreeSet::new(),
+        temperature: BTreeMap::new(),
+        turbidity: BTreeMap::new(),
+    };
+    loop {
+        select! {
+            Some(event) = receiver.recv() => {
+                info!(?event);
+                if let Event::Connected(bd_seq) = event {
+                    node.bd_seq = bd_seq;
+                }
+                node.birth().await?;
+            }
+            result = temperature_receiver.recv() => match result {
+                Ok(message) => node.temperature(message).await?,
+                Err(error @ RecvError::Lagged(_)) => {
+                    warn!(%error);
+                    metrics::lagged("sparkplug");
+                }
+                Err(error) => Err(error)?,
+            },
+            result = turbidity_receiver.recv() => match result {
+                Ok(message) => node.turbidity(message).await?,
+                Err(error @ RecvError::Lagged(_)) => {
+                    warn!(%error);
+                    metrics::lagged("sparkplug");
+                }
+                Err(error) => Err(error)?,
+            },
+        }
+    }
+}
+
+/// Polls the event loop, the birth/death sequence number of the connected
+/// session is sent with the connection and the last will of the next session
+/// is registered right away
+#[instrument(skip(event_loop))]
+async fn read(
+    mut event_loop: EventLoop,
+    client: Client,
+    topic: Topic,
+    mut bd_seq: u64,
+    sender: mpsc::Sender<Event>,
+) {
+    let command = topic.node("NCMD");
+    loop {
+        match event_loop.poll().await {
+            Ok(Some(Notification::Connected)) => {
+                if let Err(error) = client.try_subscribe(&command, QoS::AtLeastOnce) {
+                    error!(%error);
+                }
+                if let Err(error) = sender.try_send(Event::Connected(bd_seq)) {
+                    warn!(%error);
+                }
+                bd_seq = next_bd_seq();
+                event_loop.set_last_will(topic.death(bd_seq));
+            }
+            Ok(Some(Notification::Publish(publish))) if publish.topic == command => {
+                match Payload::decode(publish.payload) {
+                    Ok(payload) => {
+                        let rebirth = payload.metrics.iter().any(|metric| {
+                            metric.name.as_deref() == Some(REBIRTH)
+                                && metric.value == Some(Value::Boolean(true))
+                        });
+                        if rebirth && let Err(error) = sender.try_send(Event::Rebirth) {
+                            warn!(%error);
+                        }
+                    }
+                    Err(error) => warn!(%error),
+                }
+            }
+            Ok(notification) => trace!(?notification),
+            Err(error) => error!(?error),
+        }
+    }
+}
+
+/// Connection event
+#[derive(Clone, Copy, Debug)]
+enum Event {
+    /// Birth/death sequence number of the session
+    Connected(u64),
+    Rebirth,
+}
+
+/// Sparkplug topics
+#[derive(Clone, Copy, Debug)]
+struct Topic {
+    settings: &'static Sparkplug,
+}
+
+impl Topic {
+    fn node(self, kind: &str) -> String {
+        let Sparkplug { group, node } = self.settings;
+        format!("{NAMESPACE}/{group}/{kind}/{node}")
+    }
+
+    fn device(self, kind: &str, device: &str) -> String {
+        let Sparkplug { group, node } = self.settings;
+        format!("{NAMESPACE}/{group}/{kind}/{node}/{device}")
+    }
+
+    /// NDEATH last will of a session
+    fn death(self, bd_seq: u64) -> LastWill {
+        let timestamp = timestamp();
+        let death = Payload {
+            timestamp: Some(timestamp),
+            metrics: vec![Metric::new(BD_SEQ, timestamp, Value::Long(bd_seq))],
+            seq: None,
+        };
+        LastWill::new(
+            self.node("NDEATH"),
+            death.encode_to_vec(),
+            QoS::AtLeastOnce,
+            false,
+        )
+    }
+}
+
+/// Edge node
+struct Node {
+    client: Client,
+    topic: Topic,
+    bd_seq: u64,
+    seq: u64,
+    born: bool,
+    /// Devices born since the node birth
+    devices: BTreeSet<&'static str>,
+    temperature: BTreeMap<u64, f32>,
+    turbidity: BTreeMap<u64, u16>,
+}
+
+impl Node {
+    /// Publishes NBIRTH followed by DBIRTH of every known device
+    async fn birth(&mut self) -> Result<()> {
+        self.seq = 0;
+        let timestamp = timestamp();
+        self.publish(
+            self.topic.node("NBIRTH"),
+            timestamp,
+            vec![
+                Metric::new(BD_SEQ, timestamp, Value::Long(self.bd_seq)),
+                Metric::new(REBIRTH, timestamp, Value::Boolean(false)),
+            ],
+        )
+        .await?;
+        self.born = true;
+        self.devices.clear();
+        if !self.temperature.is_empty() {
+            let metrics = metrics(&self.temperature, timestamp, |&value| Value::Float(value));
+            self.device_birth(TEMPERATURE, timestamp, metrics).await?;
+        }
+        if !self.turbidity.is_empty() {
+            let metrics = metrics(&self.turbidity, timestamp, |&value| Value::Int(value as _));
+            self.device_birth(TURBIDITY, timestamp, metrics).await?;
+        }
+        Ok(())
+    }
+
+    /// Publishes DBIRTH, preceded by DDEATH when the device is already born
+    async fn device_birth(
+        &mut self,
+        device: &'static str,
+        timestamp: u64,
+        metrics: Vec<Metric>,
+    ) -> Result<()> {
+        if !self.devices.insert(device) {
+            let topic = self.topic.device("DDEATH", device);
+            self.publish(topic, timestamp, Vec::new()).await?;
+        }
+        let topic = self.topic.device("DBIRTH", device);
+        self.publish(topic, timestamp, metrics).await
+    }
+
+    /// Publishes DDATA, or DBIRTH when a new probe appears
+    async fn temperature(&mut self, message: TemperatureMessage) -> Result<()> {
+        let timestamp = message.date_time.timestamp_millis() as _;
+        let mut birth = false;
+        for (&identifier, &value) in message.identifiers.iter().zip(&message.values) {
+            birth |= self.temperature.insert(identifier, value).is_none();
+        }
+        if !self.born {
+            return Ok(());
+        }
+        if birth {
+            let metrics = metrics(&self.temperature, timestamp, |&value| Value::Float(value));
+            self.device_birth(TEMPERATURE, timestamp, metrics).await
+        } else {
+            let metrics = message
+                .identifiers
+                .iter()
+                .zip(message.values)
+                .map(|(identifier, value)| {
+                    Metric::new(format!("{identifier:x}"), timestamp, Value::Float(value))
+                })
+                .collect();
+            let topic = self.topic.device("DDATA", TEMPERATURE);
+            self.publish(topic, timestamp, metrics).await
+        }
+    }
+
+    /// Publishes DDATA, or DBIRTH when a new meter appears
+    async fn turbidity(&mut self, message: TurbidityMessage) -> Result<()> {
+        let timestamp = message.date_time.timestamp_millis() as _;
+        let birth = self
+            .turbidity
+            .insert(message.identifier, message.value)
+            .is_none();
+        if !self.born {
+            return Ok(());
+        }
+        if birth {
+            let metrics = metrics(&self.turbidity, timestamp, |&value| Value::Int(value as _));
+            self.device_birth(TURBIDITY, timestamp, metrics).await
+        } else {
+            let metrics = vec![Metric::new(
+                format!("{:x}", message.identifier),
+                timestamp,
+                Value::Int(message.value as _),
+            )];
+            let topic = self.topic.device("DDATA", TURBIDITY);
+            self.publish(topic, timestamp, metrics).await
+        }
+    }
+
+    async fn publish(&mut self, topic: String, timestamp: u64, metrics: Vec<Metric>) -> Result<()> {
+        let payload = Payload {
+            timestamp: Some(timestamp),
+            metrics,
+            seq: Some(self.seq),
+        };
+        self.seq = (self.seq + 1) % 256;
+        self.client
+            .publish(
+                topic,
+                QoS::AtMostOnce,
+                false,
+                payload.encode_to_vec(),
+                Properties::default(),
+            )
+            .await
+    }
+}
+
+fn metrics<T>(values: &BTreeMap<u64, T>, timestamp: u64, f: impl Fn(&T) -> Value) -> Vec<Metric> {
+    values
+        .iter()
+        .map(|(identifier, value)| Metric::new(format!("{identifier:x}"), timestamp, f(value)))
+        .collect()
+}
+
+/// Birth/death sequence number of a new session
+fn next_bd_seq() -> u64 {
+    SESSION.fetch_add(1, Ordering::Relaxed) % 256
+}
+
+fn timestamp() -> u64 {
+    Local::now().timestamp_millis() as _
+}
+
+mod payload;
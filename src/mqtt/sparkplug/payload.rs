@@ -0,0 +1,71 @@
+//! Sparkplug B payload, a subset of `sparkplug_b.proto`
+
+use prost::{Message, Oneof};
+
+/// Metric data types
+const UINT32: u32 = 7;
+const UINT64: u32 = 8;
+const FLOAT: u32 = 9;
+const BOOLEAN: u32 = 11;
+
+/// Payload
+#[derive(Clone, Message, PartialEq)]
+pub(crate) struct Payload {
+    #[prost(uint64, optional, tag = "1")]
+    pub(crate) timestamp: Option<u64>,
+    #[prost(message, repeated, tag = "2")]
+    pub(crate) metrics: Vec<Metric>,
+    #[prost(uint64, optional, tag = "3")]
+    pub(crate) seq: Option<u64>,
+}
+
+/// Metric
+#[derive(Clone, Message, PartialEq)]
+pub(crate) struct Metric {
+    #[prost(string, optional, tag = "1")]
+    pub(crate) name: Option<String>,
+    #[prost(uint64, optional, tag = "2")]
+    pub(crate) alias: Option<u64>,
+    #[prost(uint64, optional, tag = "3")]
+    pub(crate) timestamp: Option<u64>,
+    #[prost(uint32, optional, tag = "4")]
+    pub(crate) datatype: Option<u32>,
+    #[prost(oneof = "Value", tags = "10, 11, 12, 14")]
+    pub(crate) value: Option<Value>,
+}
+
+impl Metric {
+    pub(crate) fn new(name: impl Into<String>, timestamp: u64, value: Value) -> Self {
+        Self {
+            name: Some(name.into()),
+            alias: None,
+            timestamp: Some(timestamp),
+            datatype: Some(value.datatype()),
+            value: Some(value),
+        }
+    }
+}
+
+/// Metric value
+#[derive(Clone, Oneof, PartialEq)]
+pub(crate) enum Value {
+    #[prost(uint32, tag = "10")]
+    Int(u32),
+    #[prost(uint64, tag = "11")]
+    Long(u64),
+    #[prost(float, tag = "12")]
+    Float(f32),
+    #[prost(bool, tag = "14")]
+    Boolean(bool),
+}
+
+impl Value {
+    fn datatype(&self) -> u32 {
+        match self {
+            Self::Int(_) => UINT32,
+            Self::Long(_) => UINT64,
+            Self::Float(_) => FLOAT,
+            Self::Boolean(_) => BOOLEAN,
+        }
+    }
+}
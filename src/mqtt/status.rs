@@ -0,0 +1,90 @@
+use super::{
+    MQTT_TOPIC_STATUS,
+    client::{Client, Properties},
+};
+use crate::{SETTINGS, health::START, sensor::Sensor};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use rumqttc::{LastWill, QoS};
+use serde::Serialize;
+use tokio::time::{Duration, interval};
+use tracing::instrument;
+
+const CONTENT_TYPE: &str = "application/json";
+pub(super) const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Last will, published by the broker when the connection is lost
+pub(super) fn last_will() -> Result<LastWill> {
+    Ok(LastWill::new(
+        MQTT_TOPIC_STATUS,
+        serde_json::to_vec(&Status::Offline)?,
+        QoS::AtLeastOnce,
+        true,
+    ))
+}
+
+/// Publishes online status, called from the event loop on connection
+pub(super) fn online(client: &Client) -> Result<()> {
+    let status = Status::Online {
+        version: VERSION,
+        start: *START,
+    };
+    client.try_publish(
+        MQTT_TOPIC_STATUS,
+        QoS::AtLeastOnce,
+        true,
+        serde_json::to_vec(&status)?,
+        properties(),
+    )?;
+    Ok(())
+}
+
+/// Publishes sensors and probes health periodically
+#[instrument(err)]
+pub(super) async fn run(client: Client) -> Result<()> {
+    let mut interval = interval(Duration::from_secs(SETTINGS.mqtt.status));
+    loop {
+        interval.tick().await;
+        for sensor in Sensor::ALL {
+            let state = sensor.health().state();
+            for (identifier, probe) in &state.probes {
+                client
+                    .publish(
+                        format!("{MQTT_TOPIC_STATUS}/{sensor}/{identifier}"),
+                        QoS::AtLeastOnce,
+                        true,
+                        serde_json::to_vec(probe)?,
+                        properties(),
+                    )
+                    .await?;
+            }
+            client
+                .publish(
+                    format!("{MQTT_TOPIC_STATUS}/{sensor}"),
+                    QoS::AtLeastOnce,
+                    true,
+                    serde_json::to_vec(&state)?,
+                    properties(),
+                )
+                .await?;
+        }
+    }
+}
+
+fn properties() -> Properties {
+    Properties {
+        content_type: Some(CONTENT_TYPE.to_owned()),
+        ..Default::default()
+    }
+}
+
+/// Server status
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+enum Status {
+    Online {
+        version: &'static str,
+        start: DateTime<Local>,
+    },
+    Offline,
+}
@@ -1,33 +1,33 @@
-use super::MQTT_TOPIC_DTEC;
-use crate::temperature::Message;
+use super::{
+    MQTT_TOPIC_DTEC,
+    batch::{self, Batcher, Receiver, Sender},
+    client::{Client, Properties},
+};
+use crate::{SETTINGS, metrics, sensor::Sensor, temperature::Message};
 use anyhow::Result;
 use arrow::{
-    array::{Float32Array, RecordBatch, TimestampMillisecondArray, UInt64Array},
-    datatypes::{DataType, Field, Schema, TimeUnit},
-    ipc::writer::StreamWriter,
+    array::{AsArray as _, Float32Array, RecordBatch, TimestampMillisecondArray, UInt64Array},
+    datatypes::{DataType, Field, Schema, TimeUnit, UInt64Type},
 };
-use rumqttc::{AsyncClient, QoS};
+use rumqttc::QoS;
 use std::sync::Arc;
 use tokio::{
     select,
-    sync::{broadcast, watch},
+    sync::broadcast::{self, error::RecvError},
     task::{Builder, JoinHandle},
 };
-use tokio_util::{
-    bytes::{BufMut as _, BytesMut},
-    sync::CancellationToken,
-};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, instrument, warn};
 
 #[instrument(err)]
 pub(super) async fn run(
     receiver: broadcast::Receiver<Message>,
-    client: AsyncClient,
+    client: Client,
     cancellation: CancellationToken,
 ) -> Result<()> {
-    let channel = watch::channel(Message::default());
-    let reader = reader(receiver, channel.0, cancellation.clone())?;
-    let writer = writer(channel.1, client, cancellation.clone())?;
+    let (sender, channel) = batch::channel(&SETTINGS.mqtt.temperature.batch);
+    let reader = reader(receiver, sender, cancellation.clone())?;
+    let writer = writer(channel, client, cancellation.clone())?;
     select! {
         result = reader => result?,
         result = writer => result?,
@@ -37,7 +37,7 @@ pub(super) async fn run(
 
 fn reader(
     receiver: broadcast::Receiver<Message>,
-    sender: watch::Sender<Message>,
+    sender: Sender<Message>,
     cancellation: CancellationToken,
 ) -> Result<JoinHandle<()>> {
     Ok(Builder::new().name("reader").spawn(Box::pin(async move {
@@ -52,17 +52,25 @@ fn reader(
 #[instrument(err)]
 pub(crate) async fn read(
     mut receiver: broadcast::Receiver<Message>,
-    sender: watch::Sender<Message>,
+    sender: Sender<Message>,
 ) -> Result<()> {
     loop {
-        let message = receiver.recv().await?;
-        sender.send(message)?;
+        let message = match receiver.recv().await {
+            Ok(message) => message,
+            Err(error @ RecvError::Lagged(_)) => {
+                warn!(%error);
+                metrics::lagged("mqtt temperature");
+                continue;
+            }
+            Err(error) => Err(error)?,
+        };
+        sender.send(message).await?;
     }
 }
 
 fn writer(
-    receiver: watch::Receiver<Message>,
-    client: AsyncClient,
+    receiver: Receiver<Message>,
+    client: Client,
     cancellation: CancellationToken,
 ) -> Result<JoinHandle<()>> {
     Ok(Builder::new().name("writer").spawn(Box::pin(async move {
@@ -75,7 +83,7 @@ fn writer(
 }
 
 #[instrument(err)]
-async fn write(mut receiver: watch::Receiver<Message>, client: AsyncClient) -> Result<()> {
+async fn write(mut receiver: Receiver<Message>, client: Client) -> Result<()> {
     let schema = Arc::new(Schema::new(vec![
         Field::new("Identifier", DataType::UInt64, false),
         Field::new("Temperature", DataType::Float32, false),
@@ -85,33 +93,56 @@ async fn write(mut receiver: watch::Receiver<Message>, client: AsyncClient) -> R
             false,
         ),
     ]));
+    let topic = &SETTINGS.mqtt.temperature;
+    let encoding = topic.encoding;
+    let properties = Properties::reading(Sensor::Temperature, topic);
+    let mut batcher = Batcher::new(schema.clone(), &topic.batch);
     loop {
-        receiver.changed().await?;
-        let message = receiver.borrow_and_update().clone();
-        let count = message.identifiers.len();
-        let batch = RecordBatch::try_new(
-            schema.clone(),
-            vec![
-                Arc::new(UInt64Array::from(message.identifiers)),
-                Arc::new(Float32Array::from(message.values)),
-                Arc::new(TimestampMillisecondArray::from_value(
-                    message.date_time.timestamp_millis(),
-                    count,
-                )),
-            ],
-        )?;
+        select! {
+            message = receiver.recv() => {
+                let message = message?;
+                let count = message.identifiers.len();
+                let batch = RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(UInt64Array::from(message.identifiers)),
+                        Arc::new(Float32Array::from(message.values)),
+                        Arc::new(TimestampMillisecondArray::from_value(
+                            message.date_time.timestamp_millis(),
+                            count,
+                        )),
+                    ],
+                )?;
+                batcher.push(batch);
+            }
+            _ = batcher.expired() => {}
+        }
+        if !batcher.ready() {
+            continue;
+        }
+        let (batch, latest) = batcher.take()?;
         debug!(?batch);
-        let mut bytes = BytesMut::new().writer();
-        let mut writer = StreamWriter::try_new(&mut bytes, &batch.schema())?;
-        writer.write(&batch)?;
-        writer.finish()?;
         client
-            .publish_bytes(
-                MQTT_TOPIC_DTEC,
+            .publish(
+                encoding.topic(MQTT_TOPIC_DTEC),
                 QoS::ExactlyOnce,
                 false,
-                bytes.into_inner().freeze(),
+                encoding.encode(&batch, topic.compression)?,
+                properties.clone(),
             )
             .await?;
+        // Latest value per sensor
+        let identifiers = latest.column(0).as_primitive::<UInt64Type>();
+        for (index, identifier) in identifiers.values().iter().enumerate() {
+            client
+                .publish(
+                    encoding.topic(format!("{MQTT_TOPIC_DTEC}/{identifier:x}")),
+                    QoS::AtLeastOnce,
+                    true,
+                    encoding.encode(&latest.slice(index, 1), topic.compression)?,
+                    properties.clone(),
+                )
+                .await?;
+        }
     }
 }
@@ -1,23 +1,22 @@
-use std::sync::Arc;
-
-use super::MQTT_TOPIC_ATUC;
-use crate::turbidity::Message;
+use super::{
+    MQTT_TOPIC_ATUC,
+    batch::{self, Batcher, Receiver, Sender},
+    client::{Client, Properties},
+};
+use crate::{SETTINGS, metrics, sensor::Sensor, turbidity::Message};
 use anyhow::Result;
 use arrow::{
     array::{RecordBatch, TimestampMillisecondArray, UInt16Array, UInt64Array},
     datatypes::{DataType, Field, Schema, TimeUnit},
-    ipc::writer::StreamWriter,
 };
-use rumqttc::{AsyncClient, QoS};
+use rumqttc::QoS;
+use std::sync::Arc;
 use tokio::{
     select,
-    sync::{broadcast, watch},
+    sync::broadcast::{self, error::RecvError},
     task::{Builder, JoinHandle},
 };
-use tokio_util::{
-    bytes::{BufMut as _, BytesMut},
-    sync::CancellationToken,
-};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, instrument, warn};
 
 const COUNT: usize = 1;
@@ -25,12 +24,12 @@ const COUNT: usize = 1;
 #[instrument(err)]
 pub(super) async fn run(
     receiver: broadcast::Receiver<Message>,
-    client: AsyncClient,
+    client: Client,
     cancellation: CancellationToken,
 ) -> Result<()> {
-    let channel = watch::channel(Message::default());
-    let reader = reader(receiver, channel.0, cancellation.clone())?;
-    let writer = writer(channel.1, client, cancellation.clone())?;
+    let (sender, channel) = batch::channel(&SETTINGS.mqtt.turbidity.batch);
+    let reader = reader(receiver, sender, cancellation.clone())?;
+    let writer = writer(channel, client, cancellation.clone())?;
     select! {
         result = reader => result?,
         result = writer => result?,
@@ -40,7 +39,7 @@ pub(super) async fn run(
 
 fn reader(
     receiver: broadcast::Receiver<Message>,
-    sender: watch::Sender<Message>,
+    sender: Sender<Message>,
     cancellation: CancellationToken,
 ) -> Result<JoinHandle<()>> {
     Ok(Builder::new().name("reader").spawn(Box::pin(async move {
@@ -55,17 +54,25 @@ fn reader(
 #[instrument(err)]
 pub(crate) async fn read(
     mut receiver: broadcast::Receiver<Message>,
-    sender: watch::Sender<Message>,
+    sender: Sender<Message>,
 ) -> Result<()> {
     loop {
-        let message = receiver.recv().await?;
-        sender.send(message)?;
+        let message = match receiver.recv().await {
+            Ok(message) => message,
+            Err(error @ RecvError::Lagged(_)) => {
+                warn!(%error);
+                metrics::lagged("mqtt turbidity");
+                continue;
+            }
+            Err(error) => Err(error)?,
+        };
+        sender.send(message).await?;
     }
 }
 
 fn writer(
-    receiver: watch::Receiver<Message>,
-    client: AsyncClient,
+    receiver: Receiver<Message>,
+    client: Client,
     cancellation: CancellationToken,
 ) -> Result<JoinHandle<()>> {
     Ok(Builder::new().name("writer").spawn(Box::pin(async move {
@@ -78,7 +85,7 @@ fn writer(
 }
 
 #[instrument(err)]
-async fn write(mut receiver: watch::Receiver<Message>, client: AsyncClient) -> Result<()> {
+async fn write(mut receiver: Receiver<Message>, client: Client) -> Result<()> {
     let schema = Arc::new(Schema::new(vec![
         Field::new("Identifier", DataType::UInt64, false),
         Field::new("Turbidity", DataType::UInt16, false),
@@ -88,31 +95,53 @@ async fn write(mut receiver: watch::Receiver<Message>, client: AsyncClient) -> R
             false,
         ),
     ]));
+    let topic = &SETTINGS.mqtt.turbidity;
+    let encoding = topic.encoding;
+    let properties = Properties::reading(Sensor::Turbidity, topic);
+    let mut batcher = Batcher::new(schema.clone(), &topic.batch);
+    let mut identifier = 0;
     loop {
-        receiver.changed().await?;
-        let message = *receiver.borrow_and_update();
-        let batch = RecordBatch::try_new(
-            schema.clone(),
-            vec![
-                Arc::new(UInt64Array::from_value(message.identifier, COUNT)),
-                Arc::new(UInt16Array::from_value(message.value, COUNT)),
-                Arc::new(TimestampMillisecondArray::from_value(
-                    message.date_time.timestamp_millis(),
-                    COUNT,
-                )),
-            ],
-        )?;
+        select! {
+            message = receiver.recv() => {
+                let message = message?;
+                identifier = message.identifier;
+                let batch = RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(UInt64Array::from_value(message.identifier, COUNT)),
+                        Arc::new(UInt16Array::from_value(message.value, COUNT)),
+                        Arc::new(TimestampMillisecondArray::from_value(
+                            message.date_time.timestamp_millis(),
+                            COUNT,
+                        )),
+                    ],
+                )?;
+                batcher.push(batch);
+            }
+            _ = batcher.expired() => {}
+        }
+        if !batcher.ready() {
+            continue;
+        }
+        let (batch, latest) = batcher.take()?;
         debug!(?batch);
-        let mut bytes = BytesMut::new().writer();
-        let mut writer = StreamWriter::try_new(&mut bytes, &batch.schema())?;
-        writer.write(&batch)?;
-        writer.finish()?;
         client
             .publish(
-                MQTT_TOPIC_ATUC,
+                encoding.topic(MQTT_TOPIC_ATUC),
                 QoS::ExactlyOnce,
                 false,
-                bytes.into_inner().freeze(),
+                encoding.encode(&batch, topic.compression)?,
+                properties.clone(),
+            )
+            .await?;
+        // Latest value per sensor
+        client
+            .publish(
+                encoding.topic(format!("{MQTT_TOPIC_ATUC}/{identifier:x}")),
+                QoS::AtLeastOnce,
+                true,
+                encoding.encode(&latest, topic.compression)?,
+                properties.clone(),
             )
             .await?;
     }
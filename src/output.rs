@@ -0,0 +1,85 @@
+//! Modbus outputs of the controllers and the actuators
+
+use crate::settings::Output;
+use anyhow::Result;
+use std::{io, net::SocketAddr};
+use tokio::time::{Duration, sleep, timeout};
+use tokio_modbus::{client::Context, prelude::*};
+use tracing::{info, warn};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const ATTEMPTS: usize = 3;
+
+/// Connects to a Modbus device, to the TCP device unit unless given
+pub(crate) async fn connect(address: SocketAddr, unit: Option<u8>) -> io::Result<Context> {
+    match unit {
+        Some(unit) => tcp::connect_slave(address, Slave(unit)).await,
+        None => tcp::connect(address).await,
+    }
+}
+
+/// Writes the fail-safe values over a new connection, the context of the
+/// stopped device may be broken by the error that stopped it
+pub(crate) async fn restore(address: SocketAddr, unit: Option<u8>, values: &[(Output, u16)]) {
+    for attempt in 1..=ATTEMPTS {
+        match write(address, unit, values).await {
+            Ok(()) => {
+                info!("Restored {address}: {values:?}");
+                return;
+            }
+            Err(error) => warn!(%error, "restoring {address}, attempt {attempt}"),
+        }
+        if attempt < ATTEMPTS {
+            sleep(TIMEOUT).await;
+        }
+    }
+}
+
+async fn write(address: SocketAddr, unit: Option<u8>, values: &[(Output, u16)]) -> Result<()> {
+    let mut context = timeout(TIMEOUT, connect(address, unit)).await??;
+    for (output, value) in values {
+        output.write(&mut context, *value).await?;
+    }
+    Ok(())
+}
+
+impl Output {
+    /// Highest value
+    pub(crate) fn limit(&self) -> u16 {
+        match *self {
+            Self::Coil { .. } => 1,
+            Self::Register { max, .. } => max,
+        }
+    }
+
+    /// Value of an output in percent
+    pub(crate) fn scale(&self, percent: f64) -> u16 {
+        match *self {
+            Self::Coil { .. } => (percent >= 50.0) as _,
+            Self::Register { max, .. } => (percent / 100.0 * max as f64).round() as _,
+        }
+    }
+
+    pub(crate) async fn write(&self, context: &mut Context, value: u16) -> Result<()> {
+        match *self {
+            Self::Coil { address } => {
+                timeout(TIMEOUT, context.write_single_coil(address, value != 0)).await???
+            }
+            Self::Register { address, .. } => {
+                timeout(TIMEOUT, context.write_single_register(address, value)).await???
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn read(&self, context: &mut Context) -> Result<u16> {
+        Ok(match *self {
+            Self::Coil { address } => {
+                timeout(TIMEOUT, context.read_coils(address, 1)).await???[0] as _
+            }
+            Self::Register { address, .. } => {
+                timeout(TIMEOUT, context.read_holding_registers(address, 1)).await???[0]
+            }
+        })
+    }
+}
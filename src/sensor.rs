@@ -0,0 +1,117 @@
+use crate::{
+    SETTINGS,
+    health::{self, Health},
+    settings::Logger,
+};
+use anyhow::{Error, Result, bail};
+use arrow::array::{ArrayRef, RecordBatch, TimestampMillisecondArray, UInt64Array};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    sync::Arc,
+};
+
+/// Sensor kind
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Sensor {
+    Temperature,
+    Turbidity,
+}
+
+impl Sensor {
+    pub(crate) const ALL: [Self; 2] = [Self::Temperature, Self::Turbidity];
+
+    /// Name, also the logger output folder
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Temperature => "temperature",
+            Self::Turbidity => "turbidity",
+        }
+    }
+
+    /// Value column name
+    pub(crate) fn column(self) -> &'static str {
+        match self {
+            Self::Temperature => "Temperature",
+            Self::Turbidity => "Turbidity",
+        }
+    }
+
+    /// Configured unit, none for raw values
+    pub(crate) fn unit(self) -> Option<&'static str> {
+        self.settings().unit.as_deref()
+    }
+
+    pub(crate) fn settings(self) -> &'static Logger {
+        match self {
+            Self::Temperature => &SETTINGS.temperature,
+            Self::Turbidity => &SETTINGS.turbidity,
+        }
+    }
+
+    pub(crate) fn health(self) -> &'static Health {
+        match self {
+            Self::Temperature => &health::TEMPERATURE,
+            Self::Turbidity => &health::TURBIDITY,
+        }
+    }
+
+    /// Modbus gateway identifier, the address bits
+    pub(crate) fn gateway(self) -> u64 {
+        self.settings().address.ip().to_bits() as _
+    }
+
+    /// Record batch in the logger column order
+    pub(crate) fn batch(
+        self,
+        identifiers: Vec<u64>,
+        date_time: DateTime<Local>,
+        values: ArrayRef,
+    ) -> Result<RecordBatch> {
+        let count = identifiers.len();
+        Ok(RecordBatch::try_from_iter([
+            (
+                "Identifier",
+                Arc::new(UInt64Array::from(identifiers)) as ArrayRef,
+            ),
+            (
+                "Timestamp",
+                Arc::new(TimestampMillisecondArray::from_value(
+                    date_time.timestamp_millis(),
+                    count,
+                )),
+            ),
+            (self.column(), values),
+        ])?)
+    }
+}
+
+/// Sensor message readings
+pub(crate) trait Readings {
+    const SENSOR: Sensor;
+
+    fn date_time(&self) -> DateTime<Local>;
+
+    /// Identifier and value pairs
+    fn readings(&self) -> Vec<(u64, f64)>;
+}
+
+impl Display for Sensor {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Sensor {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match Self::ALL.into_iter().find(|sensor| sensor.name() == name) {
+            Some(sensor) => Ok(sensor),
+            None => bail!("unknown sensor `{name}`"),
+        }
+    }
+}
@@ -1,17 +1,45 @@
-use anyhow::Result;
+use crate::{
+    mqtt::{Compression, Encoding},
+    sensor::Sensor,
+};
+use anyhow::{Result, bail};
+use chrono::NaiveTime;
 use config::{Config, File, FileFormat};
-use serde::Deserialize;
-use std::{fs::exists, net::SocketAddrV4, path::Path};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::{self, Debug, Formatter},
+    fs::exists,
+    iter,
+    net::{SocketAddr, SocketAddrV4},
+    path::Path,
+};
 use tracing::info;
 
 static DEFAULT_CONFIG: &str = include_str!("../default_config.toml");
 static DEFAULT_CONFIG_PATH: &str = "./config.toml";
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct Settings {
     pub(crate) output: String,
     pub(crate) temperature: Logger,
     pub(crate) turbidity: Logger,
+    #[serde(default)]
+    pub(crate) mqtt: Mqtt,
+    pub(crate) http: Option<Http>,
+    pub(crate) flight: Option<Flight>,
+    #[serde(default)]
+    pub(crate) alarm: Alarm,
+    #[serde(default)]
+    pub(crate) control: Control,
+    #[serde(default)]
+    pub(crate) actuation: Actuation,
+    #[serde(default)]
+    pub(crate) dilution: Dilution,
+    pub(crate) growth: Option<Growth>,
+    #[serde(default)]
+    pub(crate) annotation: Annotation,
+    pub(crate) derived: Option<Derived>,
 }
 
 impl Settings {
@@ -33,22 +61,550 @@ impl Settings {
                 }
             }
         }
-        let settings = builder.build()?.try_deserialize()?;
+        let settings = builder.build()?.try_deserialize::<Self>()?;
+        settings.actuation.validate()?;
+        settings.dilution.validate(&settings.actuation)?;
         Ok(settings)
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct Logger {
     pub(crate) address: SocketAddrV4,
     pub(crate) count: u16,
     pub(crate) finish: usize,
     pub(crate) flush: usize,
     pub(crate) interval: u64,
+    /// Unit of the readings, none for raw values
+    pub(crate) unit: Option<String>,
+    /// Sensor names by hexadecimal identifier
+    #[serde(default)]
+    pub(crate) names: HashMap<String, String>,
+    pub(crate) watchdog: Option<Watchdog>,
+    /// Seconds of the rollup periods, a minute and an hour unless given
+    #[serde(default = "Logger::rollups")]
+    pub(crate) rollups: Vec<u64>,
 }
 
 impl Logger {
+    fn rollups() -> Vec<u64> {
+        vec![60, 3600]
+    }
+
     pub(crate) fn flush(&self) -> usize {
         self.count as usize * self.flush
     }
+
+    pub(crate) fn name(&self, identifier: u64) -> Option<&str> {
+        self.names
+            .get(&format!("{identifier:x}"))
+            .map(String::as_str)
+    }
+}
+
+/// Stale data watchdog, a limit is disabled unless given
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Watchdog {
+    /// Seconds without new data
+    pub(crate) stale: Option<u64>,
+    /// Identical consecutive samples of a probe
+    pub(crate) stuck: Option<usize>,
+    /// Consecutive samples without an expected probe
+    pub(crate) missing: Option<usize>,
+    /// Reconnects Modbus when raising
+    #[serde(default)]
+    pub(crate) reconnect: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Mqtt {
+    #[serde(default)]
+    pub(crate) version: Version,
+    /// Seconds between the sensors health publications
+    #[serde(default = "Mqtt::status")]
+    pub(crate) status: u64,
+    #[serde(default)]
+    pub(crate) temperature: Topic,
+    #[serde(default)]
+    pub(crate) turbidity: Topic,
+    pub(crate) homeassistant: Option<HomeAssistant>,
+    pub(crate) sparkplug: Option<Sparkplug>,
+}
+
+impl Mqtt {
+    fn status() -> u64 {
+        10
+    }
+}
+
+impl Default for Mqtt {
+    fn default() -> Self {
+        Self {
+            version: Version::default(),
+            status: Self::status(),
+            temperature: Topic::default(),
+            turbidity: Topic::default(),
+            homeassistant: None,
+            sparkplug: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct Topic {
+    #[serde(default)]
+    pub(crate) encoding: Encoding,
+    /// Message expiry interval in seconds (MQTT v5)
+    pub(crate) expiry: Option<u32>,
+    /// Arrow IPC body compression
+    pub(crate) compression: Option<Compression>,
+    #[serde(default)]
+    pub(crate) batch: Batch,
+}
+
+/// Readings batching, a batch is published when either limit is reached
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct Batch {
+    /// Maximum rows
+    #[serde(default)]
+    pub(crate) rows: usize,
+    /// Maximum delay in seconds
+    #[serde(default)]
+    pub(crate) delay: u64,
+}
+
+/// Home Assistant MQTT discovery
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct HomeAssistant {
+    /// Discovery prefix
+    #[serde(default = "HomeAssistant::prefix")]
+    pub(crate) prefix: String,
+}
+
+impl HomeAssistant {
+    fn prefix() -> String {
+        "homeassistant".to_owned()
+    }
+}
+
+/// Sparkplug B edge node
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Sparkplug {
+    pub(crate) group: String,
+    pub(crate) node: String,
+}
+
+/// HTTP server
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Http {
+    pub(crate) address: SocketAddr,
+}
+
+/// Arrow Flight server
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Flight {
+    pub(crate) address: SocketAddr,
+}
+
+/// Alarms
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct Alarm {
+    #[serde(default)]
+    pub(crate) rules: Vec<Rule>,
+    pub(crate) webhook: Option<Webhook>,
+    pub(crate) smtp: Option<Smtp>,
+    pub(crate) log: Option<AlarmLog>,
+}
+
+/// Alarm rule, applies to every probe of the sensor unless an identifier is
+/// given
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Rule {
+    pub(crate) name: String,
+    pub(crate) sensor: Sensor,
+    /// Hexadecimal sensor identifier
+    pub(crate) identifier: Option<String>,
+    pub(crate) high: Option<f64>,
+    pub(crate) low: Option<f64>,
+    /// Maximum absolute rate of change per minute
+    pub(crate) rate: Option<f64>,
+    /// Deadband an active alarm has to leave before clearing
+    #[serde(default)]
+    pub(crate) hysteresis: f64,
+    /// Seconds a condition has to hold before raising
+    #[serde(default)]
+    pub(crate) duration: u64,
+}
+
+/// Alarm webhook, events are posted as JSON
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Webhook {
+    pub(crate) url: String,
+}
+
+/// Alarm email
+#[derive(Deserialize, Serialize)]
+pub(crate) struct Smtp {
+    pub(crate) host: String,
+    pub(crate) port: Option<u16>,
+    #[serde(default)]
+    pub(crate) tls: Security,
+    pub(crate) username: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub(crate) password: Option<String>,
+    pub(crate) from: String,
+    pub(crate) to: Vec<String>,
+}
+
+/// The password is redacted as the settings are logged
+impl Debug for Smtp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Smtp")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("tls", &self.tls)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .finish()
+    }
+}
+
+/// SMTP connection security
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Security {
+    None,
+    Starttls,
+    #[default]
+    Tls,
+}
+
+/// Alarm log
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct AlarmLog {
+    /// Events per file
+    pub(crate) finish: usize,
+}
+
+/// Closed loop temperature control
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Control {
+    #[serde(default)]
+    pub(crate) controllers: Vec<Controller>,
+    /// Control log rows per row group
+    #[serde(default = "Control::flush")]
+    pub(crate) flush: usize,
+    /// Control log row groups per file
+    #[serde(default = "Control::finish")]
+    pub(crate) finish: usize,
+}
+
+impl Control {
+    fn flush() -> usize {
+        60
+    }
+
+    fn finish() -> usize {
+        60
+    }
+}
+
+impl Default for Control {
+    fn default() -> Self {
+        Self {
+            controllers: Vec::new(),
+            flush: Self::flush(),
+            finish: Self::finish(),
+        }
+    }
+}
+
+/// Reactor temperature controller, stepped on every reading of its probe
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Controller {
+    pub(crate) name: String,
+    /// Hexadecimal identifier of the temperature probe giving the process
+    /// value
+    pub(crate) probe: String,
+    /// Modbus device of the outputs
+    pub(crate) address: SocketAddr,
+    /// Modbus unit identifier
+    pub(crate) unit: Option<u8>,
+    /// Initial setpoint
+    pub(crate) setpoint: f64,
+    pub(crate) mode: Mode,
+    pub(crate) heater: Option<Output>,
+    pub(crate) cooler: Option<Output>,
+}
+
+/// Control mode
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum Mode {
+    /// Heats below the setpoint less the hysteresis and cools above the
+    /// setpoint plus the hysteresis, each until the setpoint is reached
+    OnOff { hysteresis: f64 },
+    /// Proportional, integral (per second) and derivative (in seconds) gains
+    /// of the output in percent
+    Pid { kp: f64, ki: f64, kd: f64 },
+}
+
+/// Modbus output
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum Output {
+    /// Switched on from half of the output
+    Coil { address: u16 },
+    /// Holding register with values up to `max`, a controller output is
+    /// scaled to `0..=max`
+    Register {
+        address: u16,
+        #[serde(default = "Output::max")]
+        max: u16,
+    },
+}
+
+impl Output {
+    fn max() -> u16 {
+        100
+    }
+}
+
+/// Actuators on Modbus relay boards
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Actuation {
+    #[serde(default)]
+    pub(crate) actuators: Vec<Actuator>,
+    /// Seconds between read back verifications
+    #[serde(default = "Actuation::verify")]
+    pub(crate) verify: u64,
+    /// Event log events per file
+    #[serde(default = "Actuation::finish")]
+    pub(crate) finish: usize,
+}
+
+impl Actuation {
+    fn verify() -> u64 {
+        60
+    }
+
+    fn finish() -> usize {
+        100
+    }
+
+    /// Checks the default and scheduled values against the output limits
+    fn validate(&self) -> Result<()> {
+        for actuator in &self.actuators {
+            let limit = actuator.output.limit();
+            let values = actuator.schedule.iter().map(|switch| switch.value);
+            for value in iter::once(actuator.default).chain(values) {
+                if value > limit {
+                    bail!(
+                        "actuator `{}`: value `{value}` above `{limit}`",
+                        actuator.name
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Actuation {
+    fn default() -> Self {
+        Self {
+            actuators: Vec::new(),
+            verify: Self::verify(),
+            finish: Self::finish(),
+        }
+    }
+}
+
+/// Pump, light or valve, the actuators of a device share its connection
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Actuator {
+    pub(crate) name: String,
+    /// Modbus device
+    pub(crate) address: SocketAddr,
+    /// Modbus unit identifier
+    pub(crate) unit: Option<u8>,
+    pub(crate) output: Output,
+    /// Safe value, written until commanded or scheduled otherwise and
+    /// whenever the device stops
+    #[serde(default)]
+    pub(crate) default: u16,
+    /// Daily schedule, a command holds until the next switch
+    #[serde(default)]
+    pub(crate) schedule: Vec<Switch>,
+}
+
+/// Scheduled value from a local time of the day
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub(crate) struct Switch {
+    pub(crate) at: NaiveTime,
+    pub(crate) value: u16,
+}
+
+/// Dilution control of continuous cultures
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Dilution {
+    #[serde(default)]
+    pub(crate) turbidostats: Vec<Turbidostat>,
+    /// Dilution log events per file
+    #[serde(default = "Dilution::finish")]
+    pub(crate) finish: usize,
+}
+
+impl Dilution {
+    fn finish() -> usize {
+        100
+    }
+
+    /// Checks the pumps and their values against the output limits
+    fn validate(&self, actuation: &Actuation) -> Result<()> {
+        for turbidostat in &self.turbidostats {
+            let Some(pump) = actuation
+                .actuators
+                .iter()
+                .find(|actuator| actuator.name == turbidostat.pump)
+            else {
+                bail!(
+                    "turbidostat `{}`: unknown pump `{}`",
+                    turbidostat.name,
+                    turbidostat.pump
+                );
+            };
+            let limit = pump.output.limit();
+            if let Some(value) = turbidostat.value
+                && value > limit
+            {
+                bail!(
+                    "turbidostat `{}`: value `{value}` above `{limit}`",
+                    turbidostat.name
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Dilution {
+    fn default() -> Self {
+        Self {
+            turbidostats: Vec::new(),
+            finish: Self::finish(),
+        }
+    }
+}
+
+/// Turbidostat, dilutes the culture whenever the smoothed turbidity exceeds
+/// the setpoint
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Turbidostat {
+    pub(crate) name: String,
+    /// Hexadecimal turbidity sensor identifier, any unless given
+    pub(crate) identifier: Option<String>,
+    pub(crate) setpoint: f64,
+    /// Weight of a new reading in the exponential moving average
+    #[serde(default = "Turbidostat::smoothing")]
+    pub(crate) smoothing: f64,
+    /// Pump actuator
+    pub(crate) pump: String,
+    /// Pump value while diluting, the output limit unless given
+    pub(crate) value: Option<u16>,
+    /// Pumping seconds per dilution
+    pub(crate) duration: u64,
+    /// Minimum seconds between dilution starts
+    pub(crate) interval: u64,
+    /// Pump flow in milliliters per second
+    pub(crate) flow: f64,
+    /// Culture volume in milliliters
+    pub(crate) volume: f64,
+}
+
+impl Turbidostat {
+    fn smoothing() -> f64 {
+        0.1
+    }
+}
+
+/// Online growth rate estimation from turbidity
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Growth {
+    /// Seconds of the regression window
+    pub(crate) window: u64,
+    /// Seconds between estimates
+    pub(crate) interval: u64,
+    /// Minimum readings of an estimate
+    #[serde(default = "Growth::minimum")]
+    pub(crate) minimum: usize,
+    /// Estimates per row group
+    pub(crate) flush: usize,
+    /// Row groups per file
+    pub(crate) finish: usize,
+}
+
+impl Growth {
+    fn minimum() -> usize {
+        10
+    }
+}
+
+/// Derived sensors, evaluated on each message of the sensors they read, are
+/// logged to the `derived` folder and published on their own topic, apart
+/// from the sensors: only `/derived/data` queries them, not the sensor routes,
+/// Grafana nor Flight
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Derived {
+    #[serde(default)]
+    pub(crate) channels: Vec<Channel>,
+    /// Readings per row group
+    pub(crate) flush: usize,
+    /// Row groups per file
+    pub(crate) finish: usize,
+}
+
+/// Derived sensor
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Channel {
+    pub(crate) name: String,
+    /// Hexadecimal identifier of the derived readings
+    pub(crate) identifier: String,
+    /// Expression over the sensor readings (e.g. `mean(temperature.28ff01,
+    /// temperature.28ff02)`)
+    pub(crate) expression: String,
+    pub(crate) unit: Option<String>,
+}
+
+/// Operator annotations log
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Annotation {
+    /// Annotations per file, queried once finished
+    #[serde(default = "Annotation::finish")]
+    pub(crate) finish: usize,
+}
+
+impl Annotation {
+    fn finish() -> usize {
+        1
+    }
+}
+
+impl Default for Annotation {
+    fn default() -> Self {
+        Self {
+            finish: Self::finish(),
+        }
+    }
+}
+
+/// MQTT protocol version
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Version {
+    #[default]
+    V3,
+    V5,
 }
@@ -1,18 +1,26 @@
-use crate::SETTINGS;
+use crate::{
+    SETTINGS,
+    health::TEMPERATURE as HEALTH,
+    metrics,
+    sensor::{Readings, Sensor},
+};
 use anyhow::Result;
+use arrow::array::{Float32Array, RecordBatch};
 use chrono::{DateTime, Local};
+use scopeguard::defer;
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::{self, Display, Formatter},
-    sync::LazyLock,
+    sync::{Arc, LazyLock},
 };
 use tokio::{
+    select,
     sync::broadcast::Sender,
     task::{Builder, JoinHandle},
     time::{Duration, interval, timeout},
 };
 use tokio_modbus::{client::Context, prelude::*};
-use tracing::{debug, instrument};
+use tracing::{debug, instrument, warn};
 
 const INPUT_REGISTER_SIZE: u16 = 6;
 const TIMEOUT: LazyLock<u64> = LazyLock::new(|| 2 * SETTINGS.temperature.interval);
@@ -23,18 +31,45 @@ pub(crate) fn spawn(sender: Sender<Message>) -> Result<JoinHandle<Result<()>>> {
 
 #[instrument(err)]
 async fn run(sender: Sender<Message>) -> Result<()> {
+    defer! {
+        HEALTH.disconnect();
+    }
+    try_run(sender).await.inspect_err(|_| HEALTH.error())
+}
+
+async fn try_run(sender: Sender<Message>) -> Result<()> {
     let mut context = tcp::connect(SETTINGS.temperature.address.into()).await?;
+    HEALTH.connect();
     let mut interval = interval(Duration::from_secs(SETTINGS.temperature.interval));
     loop {
-        interval.tick().await;
-        let message = timeout(Duration::from_secs(*TIMEOUT), read(&mut context)).await??;
+        // A reconnect request interrupts the wait and a hung read alike
+        let result = select! {
+            biased;
+            _ = HEALTH.reconnection() => {
+                warn!("temperature reconnect");
+                context = tcp::connect(SETTINGS.temperature.address.into()).await?;
+                continue;
+            }
+            result = async {
+                interval.tick().await;
+                timeout(Duration::from_secs(*TIMEOUT), read(&mut context)).await
+            } => result,
+        };
+        let message = result
+            .inspect_err(|_| metrics::read_timeout(Sensor::Temperature))?
+            .inspect_err(|_| metrics::read_error(Sensor::Temperature))?;
         debug!("temperature message: {message:x?}");
+        HEALTH.read(message.date_time, &message.identifiers);
+        for (&identifier, &value) in message.identifiers.iter().zip(&message.values) {
+            metrics::reading(Sensor::Temperature, identifier, value as _);
+        }
         sender.send(message)?;
     }
 }
 
 #[instrument(err)]
 async fn read(context: &mut Context) -> Result<Message> {
+    let _timer = metrics::read_timer(Sensor::Temperature);
     let date_time = Local::now();
     let data = context
         .read_input_registers(0, SETTINGS.temperature.count * INPUT_REGISTER_SIZE)
@@ -69,6 +104,32 @@ pub(crate) struct Message {
     pub(crate) values: Vec<f32>,
 }
 
+impl Message {
+    pub(crate) fn batch(self) -> Result<RecordBatch> {
+        Sensor::Temperature.batch(
+            self.identifiers,
+            self.date_time,
+            Arc::new(Float32Array::from(self.values)),
+        )
+    }
+}
+
+impl Readings for Message {
+    const SENSOR: Sensor = Sensor::Temperature;
+
+    fn date_time(&self) -> DateTime<Local> {
+        self.date_time
+    }
+
+    fn readings(&self) -> Vec<(u64, f64)> {
+        self.identifiers
+            .iter()
+            .zip(&self.values)
+            .map(|(&identifier, &value)| (identifier, value as _))
+            .collect()
+    }
+}
+
 impl Display for Message {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         writeln!(
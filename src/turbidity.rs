@@ -1,18 +1,26 @@
-use crate::SETTINGS;
+use crate::{
+    SETTINGS,
+    health::TURBIDITY as HEALTH,
+    metrics,
+    sensor::{Readings, Sensor},
+};
 use anyhow::Result;
+use arrow::array::{RecordBatch, UInt16Array};
 use chrono::{DateTime, Local};
+use scopeguard::defer;
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::{self, Display, Formatter},
-    sync::LazyLock,
+    sync::{Arc, LazyLock},
 };
 use tokio::{
+    select,
     sync::broadcast::Sender,
     task::{Builder, JoinHandle},
     time::{Duration, interval, timeout},
 };
 use tokio_modbus::{client::Context, prelude::*};
-use tracing::{debug, instrument};
+use tracing::{debug, instrument, warn};
 
 // const ID: u64 = 0xc0a80094;
 
@@ -25,20 +33,45 @@ pub(crate) fn spawn(sender: Sender<Message>) -> Result<JoinHandle<Result<()>>> {
 
 #[instrument(err)]
 async fn run(sender: Sender<Message>) -> Result<()> {
+    defer! {
+        HEALTH.disconnect();
+    }
+    try_run(sender).await.inspect_err(|_| HEALTH.error())
+}
+
+async fn try_run(sender: Sender<Message>) -> Result<()> {
     let mut context = tcp::connect(SETTINGS.turbidity.address.into()).await?;
+    HEALTH.connect();
     let mut interval = interval(Duration::from_secs(SETTINGS.turbidity.interval));
     loop {
-        interval.tick().await;
-        let message = timeout(Duration::from_secs(*TIMEOUT), read(&mut context)).await??;
+        // A reconnect request interrupts the wait and a hung read alike
+        let result = select! {
+            biased;
+            _ = HEALTH.reconnection() => {
+                warn!("turbidity reconnect");
+                context = tcp::connect(SETTINGS.turbidity.address.into()).await?;
+                continue;
+            }
+            result = async {
+                interval.tick().await;
+                timeout(Duration::from_secs(*TIMEOUT), read(&mut context)).await
+            } => result,
+        };
+        let message = result
+            .inspect_err(|_| metrics::read_timeout(Sensor::Turbidity))?
+            .inspect_err(|_| metrics::read_error(Sensor::Turbidity))?;
         debug!("turbidity message: {message}");
+        HEALTH.read(message.date_time, &[message.identifier]);
+        metrics::reading(Sensor::Turbidity, message.identifier, message.value as _);
         sender.send(message)?;
     }
 }
 
 #[instrument(err)]
 async fn read(context: &mut Context) -> Result<Message> {
+    let _timer = metrics::read_timer(Sensor::Turbidity);
     let date_time = Local::now();
-    let identifier = SETTINGS.turbidity.address.ip().to_bits() as _;
+    let identifier = Sensor::Turbidity.gateway();
     let data = context
         .read_input_registers(0, SETTINGS.turbidity.count * INPUT_REGISTER_SIZE)
         .await??;
@@ -56,6 +89,28 @@ pub(crate) struct Message {
     pub(crate) value: u16,
 }
 
+impl Message {
+    pub(crate) fn batch(self) -> Result<RecordBatch> {
+        Sensor::Turbidity.batch(
+            vec![self.identifier],
+            self.date_time,
+            Arc::new(UInt16Array::from(vec![self.value])),
+        )
+    }
+}
+
+impl Readings for Message {
+    const SENSOR: Sensor = Sensor::Turbidity;
+
+    fn date_time(&self) -> DateTime<Local> {
+        self.date_time
+    }
+
+    fn readings(&self) -> Vec<(u64, f64)> {
+        vec![(self.identifier, self.value as _)]
+    }
+}
+
 impl Display for Message {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         writeln!(
@@ -0,0 +1,56 @@
+use super::Dilution;
+use crate::{
+    SETTINGS,
+    logger::{Log, Policy},
+};
+use anyhow::Result;
+use arrow::{
+    array::{Float64Array, RecordBatch, StringArray, TimestampMillisecondArray},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::instrument;
+
+const DILUTION: &str = "dilution";
+
+/// Writes dilutions to the dilution log, a row group per dilution
+#[instrument(err, skip(receiver))]
+pub(super) async fn run(receiver: mpsc::Receiver<Dilution>) -> Result<()> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(
+            "Timestamp",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            false,
+        ),
+        Field::new("Turbidostat", DataType::Utf8, false),
+        Field::new("Turbidity", DataType::Float64, false),
+        Field::new("Setpoint", DataType::Float64, false),
+        Field::new("Volume", DataType::Float64, false),
+        Field::new("Fraction", DataType::Float64, false),
+        Field::new("Total", DataType::Float64, false),
+    ]));
+    let policy = Policy {
+        flush: 1,
+        finish: SETTINGS.dilution.finish,
+    };
+    Log::new(DILUTION, schema, policy)
+        .run(receiver, |schema, dilution| {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(TimestampMillisecondArray::from(vec![
+                        dilution.date_time.timestamp_millis(),
+                    ])),
+                    Arc::new(StringArray::from(vec![dilution.turbidostat])),
+                    Arc::new(Float64Array::from(vec![dilution.turbidity])),
+                    Arc::new(Float64Array::from(vec![dilution.setpoint])),
+                    Arc::new(Float64Array::from(vec![dilution.volume])),
+                    Arc::new(Float64Array::from(vec![dilution.fraction])),
+                    Arc::new(Float64Array::from(vec![dilution.total])),
+                ],
+            )?;
+            Ok((dilution.date_time, batch))
+        })
+        .await
+}
@@ -0,0 +1,307 @@
+use crate::{
+    SETTINGS,
+    actuator::{ACTUATORS, Source},
+    metrics,
+    settings::{Actuator, Turbidostat},
+    turbidity::Message as TurbidityMessage,
+};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use futures_util::future::{join, join_all};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    io,
+    sync::{LazyLock, Mutex, PoisonError},
+};
+use tokio::{
+    select,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc,
+    },
+    task::{Builder, JoinHandle},
+    time::{Duration, Instant, sleep, sleep_until},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, instrument, warn};
+
+const SLEEP: Duration = Duration::from_secs(10);
+const CHANNEL_BUFFER: usize = 64;
+const CHANNEL_LENGTH: usize = 64;
+
+/// Turbidostat states, shared with the HTTP server and the MQTT commander
+pub(crate) static TURBIDOSTATS: LazyLock<Turbidostats> = LazyLock::new(Turbidostats::new);
+
+pub(crate) fn spawn(
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: CancellationToken,
+) -> io::Result<JoinHandle<()>> {
+    Builder::new()
+        .name("turbidostat")
+        .spawn(Box::pin(async move {
+            loop {
+                // Returns once the pumps are released, also when cancelled
+                let _ = run(turbidity_receiver.resubscribe(), &cancellation).await;
+                if cancellation.is_cancelled() {
+                    warn!("turbidostat cancelled");
+                    break;
+                }
+                warn!("loop turbidostat");
+                select! {
+                    biased;
+                    _ = cancellation.cancelled() => {
+                        warn!("turbidostat cancelled");
+                        break;
+                    }
+                    _ = sleep(SLEEP) => {},
+                };
+            }
+        }))
+}
+
+#[instrument(err, skip(cancellation))]
+async fn run(
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: &CancellationToken,
+) -> Result<()> {
+    if SETTINGS.dilution.turbidostats.is_empty() {
+        cancellation.cancelled().await;
+        return Ok(());
+    }
+    // Stops the turbidostats when the log stops
+    let stop = cancellation.child_token();
+    let (sender, receiver) = mpsc::channel(CHANNEL_BUFFER);
+    let turbidostats = join_all(
+        SETTINGS
+            .dilution
+            .turbidostats
+            .iter()
+            .map(|settings| retry(settings, &turbidity_receiver, sender.clone(), &stop)),
+    );
+    drop(sender);
+    let log = async {
+        let result = log::run(receiver).await;
+        stop.cancel();
+        result
+    };
+    join(turbidostats, log).await.1
+}
+
+/// Restarts a turbidostat until stopped, a failure only releases its own pump
+async fn retry(
+    settings: &'static Turbidostat,
+    receiver: &broadcast::Receiver<TurbidityMessage>,
+    sender: mpsc::Sender<Dilution>,
+    stop: &CancellationToken,
+) {
+    loop {
+        // Returns once the pump is released, also when stopped
+        let _ = turbidostat(settings, receiver.resubscribe(), sender.clone(), stop).await;
+        if stop.is_cancelled() {
+            break;
+        }
+        warn!("loop turbidostat {}", settings.name);
+        select! {
+            biased;
+            _ = stop.cancelled() => break,
+            _ = sleep(SLEEP) => {},
+        };
+    }
+}
+
+/// Dilutes the culture through the pump actuator until stopped, the pump is
+/// released to its default on start and on every exit
+#[instrument(err, skip_all, fields(turbidostat = %settings.name))]
+async fn turbidostat(
+    settings: &'static Turbidostat,
+    receiver: broadcast::Receiver<TurbidityMessage>,
+    sender: mpsc::Sender<Dilution>,
+    stop: &CancellationToken,
+) -> Result<()> {
+    let Some(pump) = SETTINGS
+        .actuation
+        .actuators
+        .iter()
+        .find(|actuator| actuator.name == settings.pump)
+    else {
+        bail!("unknown pump `{}`", settings.pump);
+    };
+    // A dilution of a previous run may still hold the pump
+    ACTUATORS.release(&pump.name, Source::Turbidostat);
+    let result = select! {
+        biased;
+        _ = stop.cancelled() => Ok(()),
+        result = regulate(settings, pump, receiver, sender) => result,
+    };
+    TURBIDOSTATS.pumping(&settings.name, false);
+    ACTUATORS.release(&pump.name, Source::Turbidostat);
+    result
+}
+
+async fn regulate(
+    settings: &'static Turbidostat,
+    pump: &Actuator,
+    mut receiver: broadcast::Receiver<TurbidityMessage>,
+    sender: mpsc::Sender<Dilution>,
+) -> Result<()> {
+    let identifier = settings
+        .identifier
+        .as_deref()
+        .map(|identifier| u64::from_str_radix(identifier, 16))
+        .transpose()?;
+    let value = settings.value.unwrap_or(pump.output.limit());
+    let duration = Duration::from_secs(settings.duration);
+    let interval = Duration::from_secs(settings.interval);
+    let mut smoothed = None;
+    let mut start = None::<Instant>;
+    let mut stop = None;
+    loop {
+        select! {
+            _ = sleep_until(stop.unwrap_or_else(Instant::now)), if stop.is_some() => {
+                stop = None;
+                // A command or a schedule may have taken the pump since
+                ACTUATORS.release(&pump.name, Source::Turbidostat);
+                TURBIDOSTATS.pumping(&settings.name, false);
+            }
+            message = receiver.recv() => {
+                let message = match message {
+                    Ok(message) => message,
+                    Err(error @ RecvError::Lagged(_)) => {
+                        warn!(%error);
+                        metrics::lagged("turbidostat");
+                        continue;
+                    }
+                    Err(error) => Err(error)?,
+                };
+                if identifier.is_some_and(|identifier| identifier != message.identifier) {
+                    continue;
+                }
+                let reading = message.value as f64;
+                let turbidity = *smoothed.insert(match smoothed {
+                    Some(smoothed) => smoothed + settings.smoothing * (reading - smoothed),
+                    None => reading,
+                });
+                TURBIDOSTATS.turbidity(&settings.name, turbidity);
+                if turbidity <= settings.setpoint
+                    || stop.is_some()
+                    || start.is_some_and(|start| start.elapsed() < interval)
+                {
+                    continue;
+                }
+                let now = Instant::now();
+                start = Some(now);
+                stop = Some(now + duration);
+                ACTUATORS.set(&pump.name, value, Source::Turbidostat)?;
+                let dilution = TURBIDOSTATS.dilute(settings, message.date_time, turbidity);
+                sender.send(dilution).await?;
+            }
+        }
+    }
+}
+
+/// Turbidostat states by name, with the dilutions sender
+pub(crate) struct Turbidostats {
+    states: Mutex<BTreeMap<String, State>>,
+    sender: broadcast::Sender<Dilution>,
+}
+
+impl Turbidostats {
+    fn new() -> Self {
+        let states = SETTINGS
+            .dilution
+            .turbidostats
+            .iter()
+            .map(|turbidostat| (turbidostat.name.clone(), State::default()))
+            .collect();
+        Self {
+            states: Mutex::new(states),
+            sender: broadcast::channel(CHANNEL_LENGTH).0,
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<Dilution> {
+        self.sender.subscribe()
+    }
+
+    pub(crate) fn states(&self) -> BTreeMap<String, State> {
+        self.states
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn turbidity(&self, turbidostat: &str, turbidity: f64) {
+        let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(state) = states.get_mut(turbidostat) {
+            state.turbidity = Some(turbidity);
+        }
+    }
+
+    fn pumping(&self, turbidostat: &str, pumping: bool) {
+        let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(state) = states.get_mut(turbidostat) {
+            state.pumping = pumping;
+        }
+    }
+
+    /// Accounts a dilution
+    fn dilute(
+        &self,
+        settings: &Turbidostat,
+        date_time: DateTime<Local>,
+        turbidity: f64,
+    ) -> Dilution {
+        let mut states = self.states.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = states.entry(settings.name.clone()).or_default();
+        let volume = settings.flow * settings.duration as f64;
+        state.pumping = true;
+        state.dilutions += 1;
+        state.total += volume;
+        let dilution = Dilution {
+            turbidostat: settings.name.clone(),
+            date_time,
+            turbidity,
+            setpoint: settings.setpoint,
+            volume,
+            fraction: volume / settings.volume,
+            total: state.total,
+        };
+        info!(?dilution);
+        state.last = Some(dilution.clone());
+        // No growth estimator is not an error
+        let _ = self.sender.send(dilution.clone());
+        dilution
+    }
+}
+
+/// Turbidostat state
+#[derive(Clone, Debug, Default, Serialize)]
+pub(crate) struct State {
+    /// Smoothed turbidity
+    pub(crate) turbidity: Option<f64>,
+    pub(crate) pumping: bool,
+    pub(crate) dilutions: u64,
+    /// Milliliters pumped in total
+    pub(crate) total: f64,
+    /// Latest dilution
+    pub(crate) last: Option<Dilution>,
+}
+
+/// Dilution event
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Dilution {
+    pub(crate) turbidostat: String,
+    pub(crate) date_time: DateTime<Local>,
+    /// Smoothed turbidity at the start
+    pub(crate) turbidity: f64,
+    pub(crate) setpoint: f64,
+    /// Milliliters pumped
+    pub(crate) volume: f64,
+    /// Fraction of the culture volume replaced
+    pub(crate) fraction: f64,
+    /// Milliliters pumped in total
+    pub(crate) total: f64,
+}
+
+mod log;
@@ -0,0 +1,199 @@
+use crate::{
+    alarm::{ALARMS, Condition},
+    metrics,
+    sensor::{Readings, Sensor},
+    settings::Watchdog,
+    temperature::Message as TemperatureMessage,
+    turbidity::Message as TurbidityMessage,
+};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use std::{collections::BTreeMap, future::pending, io};
+use tokio::{
+    select,
+    sync::broadcast::{self, error::RecvError},
+    task::{Builder, JoinHandle},
+    time::{Duration, interval, sleep},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{instrument, warn};
+
+const SLEEP: Duration = Duration::from_secs(10);
+const INTERVAL: Duration = Duration::from_secs(1);
+
+pub(crate) fn spawn(
+    temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+    cancellation: CancellationToken,
+) -> io::Result<JoinHandle<()>> {
+    Builder::new().name("watchdog").spawn(Box::pin(async move {
+        loop {
+            select! {
+                biased;
+                _ = cancellation.cancelled() => {
+                    warn!("watchdog cancelled");
+                    break;
+                }
+                _ = run(temperature_receiver.resubscribe(), turbidity_receiver.resubscribe()) => {},
+            };
+            warn!("loop watchdog");
+            sleep(SLEEP).await;
+        }
+    }))
+}
+
+#[instrument(err)]
+async fn run(
+    temperature_receiver: broadcast::Receiver<TemperatureMessage>,
+    turbidity_receiver: broadcast::Receiver<TurbidityMessage>,
+) -> Result<()> {
+    select! {
+        result = watch(temperature_receiver) => result?,
+        result = watch(turbidity_receiver) => result?,
+    }
+    Ok(())
+}
+
+/// Watches the messages of a sensor, stale data is checked every second
+async fn watch<T: Clone + Readings>(mut receiver: broadcast::Receiver<T>) -> Result<()> {
+    let Some(settings) = &T::SENSOR.settings().watchdog else {
+        return pending().await;
+    };
+    let mut watchdog = State::new(T::SENSOR, settings);
+    let mut interval = interval(INTERVAL);
+    loop {
+        select! {
+            message = receiver.recv() => match message {
+                Ok(message) => watchdog.message(message.date_time(), message.readings()),
+                Err(error @ RecvError::Lagged(_)) => {
+                    warn!(%error);
+                    metrics::lagged("watchdog");
+                }
+                Err(error) => Err(error)?,
+            },
+            _ = interval.tick() => watchdog.tick(Local::now()),
+        }
+    }
+}
+
+/// Watchdog state of a sensor
+struct State<'a> {
+    sensor: Sensor,
+    settings: &'a Watchdog,
+    last: DateTime<Local>,
+    /// Probes by identifier, the named ones are expected from the start
+    probes: BTreeMap<u64, Probe>,
+}
+
+/// Probe counters
+#[derive(Default)]
+struct Probe {
+    value: Option<f64>,
+    repeated: usize,
+    missing: usize,
+}
+
+impl<'a> State<'a> {
+    fn new(sensor: Sensor, settings: &'a Watchdog) -> Self {
+        let probes = sensor
+            .settings()
+            .names
+            .keys()
+            .filter_map(|identifier| u64::from_str_radix(identifier, 16).ok())
+            .map(|identifier| (identifier, Probe::default()))
+            .collect();
+        Self {
+            sensor,
+            settings,
+            last: Local::now(),
+            probes,
+        }
+    }
+
+    fn message(&mut self, date_time: DateTime<Local>, readings: Vec<(u64, f64)>) {
+        self.last = date_time;
+        self.tick(date_time);
+        for probe in self.probes.values_mut() {
+            probe.missing += 1;
+        }
+        for (identifier, value) in readings {
+            let probe = self.probes.entry(identifier).or_default();
+            if probe.value == Some(value) {
+                probe.repeated += 1;
+            } else {
+                probe.value = Some(value);
+                probe.repeated = 1;
+            }
+            probe.missing = 0;
+            if let Some(stuck) = self.settings.stuck {
+                let violated = probe.repeated >= stuck;
+                self.alarm(identifier, Condition::Stuck, date_time, value, violated);
+            }
+        }
+        for (&identifier, probe) in &self.probes {
+            let stuck = self
+                .settings
+                .stuck
+                .is_some_and(|stuck| probe.repeated >= stuck);
+            let missing = self
+                .settings
+                .missing
+                .is_some_and(|missing| probe.missing >= missing);
+            self.sensor
+                .health()
+                .probe_stale(identifier, stuck || missing);
+        }
+        if let Some(missing) = self.settings.missing {
+            let probes = self
+                .probes
+                .iter()
+                .map(|(&identifier, probe)| (identifier, probe.missing))
+                .collect::<Vec<_>>();
+            for (identifier, count) in probes {
+                let violated = count >= missing;
+                self.alarm(
+                    identifier,
+                    Condition::Missing,
+                    date_time,
+                    count as _,
+                    violated,
+                );
+            }
+        }
+    }
+
+    /// Checks for stale data
+    fn tick(&mut self, date_time: DateTime<Local>) {
+        let Some(stale) = self.settings.stale else {
+            return;
+        };
+        let elapsed = (date_time - self.last).num_milliseconds() as f64 / 1000.0;
+        let violated = elapsed > stale as _;
+        self.sensor.health().stale(violated);
+        let gateway = self.sensor.gateway();
+        self.alarm(gateway, Condition::Stale, date_time, elapsed, violated);
+    }
+
+    /// Raises or clears an alarm, reconnecting Modbus on raise if configured
+    fn alarm(
+        &self,
+        identifier: u64,
+        condition: Condition,
+        date_time: DateTime<Local>,
+        value: f64,
+        violated: bool,
+    ) {
+        let raised = ALARMS.watchdog(
+            self.sensor,
+            identifier,
+            condition,
+            date_time,
+            value,
+            violated,
+        );
+        if raised && self.settings.reconnect {
+            warn!("{} {condition}, reconnect", self.sensor);
+            self.sensor.health().request_reconnect();
+        }
+    }
+}